@@ -1,74 +1,193 @@
-use anyhow::{anyhow, ensure, Result};
-use ethers::prelude::coins_bip39::English;
+use anyhow::Result;
+use ethers::prelude::coins_bip39::{English, Mnemonic};
 use ethers::prelude::k256::ecdsa::SigningKey;
 use ethers::prelude::transaction::eip2718::TypedTransaction;
 use ethers::prelude::Signature;
-use ethers::signers::{LocalWallet, MnemonicBuilder, Signer, Wallet};
+use ethers::signers::{LocalWallet, MnemonicBuilder, Wallet};
 use ethers::types::{Address as WalletAddress, Bytes};
+use std::ops::Range;
+use std::sync::Arc;
 
-pub struct Account {
-    pub wallet: Wallet<SigningKey>,
+use crate::error::{AccountError, AccountResult};
+use crate::signer::AccountSigner;
+use crate::{typed_data, verify};
+use ethers::types::transaction::eip712::Eip712;
+
+/// The default BIP-44 parent path for ethereum accounts, the account index is appended to it.
+pub const DEFAULT_DERIVATION_PATH: &str = "m/44'/60'/0'/0";
+
+const PRIVATE_KEY_LENGTH: usize = 32;
+
+/// An account signing through `S`, an in-memory wallet by default.
+///
+/// Cloning only bumps reference counts, so one account can be shared by many tokio tasks.
+pub struct Account<S = Wallet<SigningKey>> {
+    pub signer: Arc<S>,
     pub address: WalletAddress,
+    /// The derivation path the wallet was built from, `None` for private key accounts.
+    pub derivation_path: Option<Arc<str>>,
+}
+
+impl<S> Clone for Account<S> {
+    fn clone(&self) -> Self {
+        Self {
+            signer: Arc::clone(&self.signer),
+            address: self.address,
+            derivation_path: self.derivation_path.clone(),
+        }
+    }
 }
 
 impl Account {
-    pub fn new(key: KeyOpt) -> Result<Self> {
-        ensure!(!key.phrase_key.is_empty() || !key.private_key.is_empty());
+    pub fn new(key: KeyOpt) -> AccountResult<Self> {
+        if key.phrase_key.is_empty() && key.private_key.is_empty() {
+            return Err(AccountError::MissingKey);
+        }
         if !key.phrase_key.is_empty() && !key.private_key.is_empty() {
-            return Err(anyhow!(
-                "private key and phrase key are ambigous, one is enough"
-            ));
+            return Err(AccountError::AmbiguousKey);
         }
 
-        if key.phrase_key.is_empty() {
-            let wallet = MnemonicBuilder::<English>::default()
-                .phrase(key.phrase_key.as_str())
-                .index(0u32)
-                .unwrap()
-                .build()
-                .unwrap();
-            let acc = Account {
-                address: format!("{:#x}", wallet.address())
-                    .parse::<WalletAddress>()
-                    .unwrap(),
-                wallet,
-            };
-
-            return Ok(acc);
+        if !key.phrase_key.is_empty() {
+            if key.account_indexes.is_some() {
+                return Err(AccountError::UnexpectedAccountIndexes);
+            }
+
+            let path = key.derivation_path_at(key.account_index)?;
+            return Self::from_phrase(key.phrase_key.as_str(), path);
         }
 
-        let wallet: LocalWallet = key.private_key.parse().unwrap();
+        let wallet = parse_private_key(key.private_key.as_str())?;
+
+        Ok(Account::from_signer(wallet))
+    }
+
+    /// Derive one account per index of `KeyOpt::with_account_indexes` from the same phrase,
+    /// e.g. `m/44'/60'/0'/0/{0..N}`. Without an index range a single account is returned.
+    pub fn new_batch(key: KeyOpt) -> AccountResult<Vec<Self>> {
+        if key.phrase_key.is_empty() {
+            return Err(AccountError::MissingKey);
+        }
+        if !key.private_key.is_empty() {
+            return Err(AccountError::AmbiguousKey);
+        }
 
-        let acc = Account {
-            address: format!("{:#x}", wallet.address())
-                .parse::<WalletAddress>()
-                .unwrap(),
-            wallet,
+        let indexes = match key.account_indexes.clone() {
+            Some(indexes) => indexes,
+            None => return Ok(vec![Self::new(key)?]),
         };
 
-        Ok(acc)
+        let mut accounts = Vec::with_capacity(indexes.len());
+        for index in indexes {
+            let path = key.derivation_path_at(Some(index))?;
+            accounts.push(Self::from_phrase(key.phrase_key.as_str(), path)?);
+        }
+
+        Ok(accounts)
+    }
+
+    fn from_phrase(phrase: &str, path: String) -> AccountResult<Self> {
+        let words = phrase.split_whitespace().collect::<Vec<&str>>();
+        if !matches!(words.len(), 12 | 15 | 18 | 21 | 24) {
+            return Err(AccountError::InvalidMnemonicLength(words.len()));
+        }
+
+        let phrase = words.join(" ");
+        Mnemonic::<English>::new_from_phrase(&phrase)
+            .map_err(|e| AccountError::from_mnemonic_error(e, words.len()))?;
+
+        let wallet = MnemonicBuilder::<English>::default()
+            .phrase(phrase.as_str())
+            .derivation_path(path.as_str())
+            .map_err(|_| AccountError::InvalidDerivationPath(path.clone()))?
+            .build()?;
+
+        Ok(Account {
+            derivation_path: Some(path.into()),
+            ..Account::from_signer(wallet)
+        })
+    }
+}
+
+fn parse_private_key(private_key: &str) -> AccountResult<LocalWallet> {
+    let private_key = private_key.trim();
+    let private_key = private_key.strip_prefix("0x").unwrap_or(private_key);
+    let key_bytes =
+        hex::decode(private_key).map_err(|e| AccountError::InvalidHex(e.to_string()))?;
+    if key_bytes.len() != PRIVATE_KEY_LENGTH {
+        return Err(AccountError::InvalidKeyLength {
+            expected: PRIVATE_KEY_LENGTH,
+            actual: key_bytes.len(),
+        });
+    }
+
+    LocalWallet::from_bytes(&key_bytes).map_err(|_| AccountError::InvalidPrivateKey)
+}
+
+impl<S: AccountSigner> Account<S> {
+    /// Build an account backed by any signer, e.g. a `RemoteSigner`.
+    pub fn from_signer(signer: S) -> Self {
+        Self::from_shared_signer(Arc::new(signer))
+    }
+
+    /// Build an account backed by a signer shared with other accounts or services.
+    pub fn from_shared_signer(signer: Arc<S>) -> Self {
+        Self {
+            address: signer.address(),
+            signer,
+            derivation_path: None,
+        }
     }
 
     pub fn account_address(&self) -> Result<String> {
         Ok(format!("{:#x}", &self.address))
     }
 
-    pub async fn sign_message(self, msg: &str) -> Result<Signature> {
-        let res = self.wallet.sign_message(msg).await?;
+    pub async fn sign_message(&self, msg: &str) -> Result<Signature> {
+        let res = self.signer.sign_message(msg.as_bytes()).await?;
         Ok(res)
     }
 
-    pub async fn sign_tx(self, tx: &TypedTransaction) -> Result<Bytes> {
-        let res = self.wallet.sign_transaction(tx).await?;
-        let tx_bytes = tx.rlp_signed(&res);
+    pub async fn sign_tx(&self, tx: &TypedTransaction) -> Result<Bytes> {
+        let tx_bytes = self.signer.sign_transaction(tx).await?;
         Ok(tx_bytes)
     }
+
+    /// Check the message was personal signed by this account.
+    pub fn verify(&self, msg: &str, signature: &Signature) -> Result<bool> {
+        verify::verify_message(msg, signature, self.address)
+    }
+
+    /// Check the signed raw transaction was signed by this account.
+    pub fn verify_tx(&self, raw_tx: &Bytes) -> Result<bool> {
+        verify::verify_tx(raw_tx, self.address)
+    }
+
+    /// Check the EIP-712 payload was signed by this account.
+    pub fn verify_typed_data<T: Eip712>(&self, payload: &T, signature: &Signature) -> Result<bool> {
+        typed_data::verify_typed_data(payload, signature, self.address)
+    }
+
+    /// EIP-712 sign a struct deriving `Eip712`, e.g. a Permit or a limit order.
+    pub async fn sign_typed_data<T: Eip712 + Send + Sync>(&self, payload: &T) -> Result<Signature> {
+        let res = self.signer.sign_typed_data(payload).await?;
+        Ok(res)
+    }
+
+    /// EIP-712 sign an `eth_signTypedData_v4` JSON document.
+    pub async fn sign_typed_data_json(&self, typed_data_json: &str) -> Result<Signature> {
+        let typed_data = typed_data::parse_typed_data(typed_data_json)?;
+        let res = self.signer.sign_typed_data_json(&typed_data).await?;
+        Ok(res)
+    }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct KeyOpt {
     pub(crate) private_key: String,
     pub(crate) phrase_key: String,
+    pub(crate) derivation_path: Option<String>,
+    pub(crate) account_index: Option<u32>,
+    pub(crate) account_indexes: Option<Range<u32>>,
 }
 
 impl KeyOpt {
@@ -85,4 +204,37 @@ impl KeyOpt {
             ..KeyOpt::default()
         }
     }
+
+    /// Set the derivation path of a phrase key. Used as is when no account index is set,
+    /// otherwise it's the parent path the index is appended to.
+    pub fn with_derivation_path(mut self, derivation_path: String) -> Self {
+        self.derivation_path = Some(derivation_path);
+        self
+    }
+
+    /// Set the account index of a phrase key, default to 0.
+    pub fn with_account_index(mut self, account_index: u32) -> Self {
+        self.account_index = Some(account_index);
+        self
+    }
+
+    /// Set a range of account indexes of a phrase key, used by `Account::new_batch`.
+    pub fn with_account_indexes(mut self, account_indexes: Range<u32>) -> Self {
+        self.account_indexes = Some(account_indexes);
+        self
+    }
+
+    fn derivation_path_at(&self, index: Option<u32>) -> AccountResult<String> {
+        if self.account_index.is_some() && self.account_indexes.is_some() {
+            return Err(AccountError::AmbiguousAccountIndex);
+        }
+
+        let path = match (&self.derivation_path, index) {
+            (Some(path), None) => path.clone(),
+            (Some(path), Some(index)) => format!("{}/{}", path.trim_end_matches('/'), index),
+            (None, index) => format!("{}/{}", DEFAULT_DERIVATION_PATH, index.unwrap_or(0)),
+        };
+
+        Ok(path)
+    }
 }
@@ -0,0 +1,61 @@
+use ethers::prelude::coins_bip39::{MnemonicError, WordlistError};
+use ethers::signers::WalletError;
+use std::path::PathBuf;
+use thiserror::Error;
+
+pub type AccountResult<T> = std::result::Result<T, AccountError>;
+
+/// Errors building an `Account` from key material.
+#[derive(Error, Debug)]
+pub enum AccountError {
+    #[error("either private key or phrase key is required")]
+    MissingKey,
+    #[error("private key and phrase key are ambigous, one is enough")]
+    AmbiguousKey,
+    #[error("account index and account index range are ambigous, one is enough")]
+    AmbiguousAccountIndex,
+    #[error("account index range is set, use Account::new_batch instead")]
+    UnexpectedAccountIndexes,
+    #[error("invalid hex in private key: {0}")]
+    InvalidHex(String),
+    #[error("invalid private key length: expected {expected} bytes, found {actual}")]
+    InvalidKeyLength { expected: usize, actual: usize },
+    #[error("invalid private key: not a valid secp256k1 scalar")]
+    InvalidPrivateKey,
+    #[error("invalid mnemonic word: `{0}`")]
+    InvalidMnemonicWord(String),
+    #[error("invalid mnemonic checksum")]
+    InvalidMnemonicChecksum,
+    #[error("invalid mnemonic word count: expected 12, 15, 18, 21 or 24, found {0}")]
+    InvalidMnemonicLength(usize),
+    #[error("invalid derivation path: `{0}`")]
+    InvalidDerivationPath(String),
+    #[error("invalid keystore password")]
+    InvalidKeystorePassword,
+    #[error("malformed keystore: {0}")]
+    MalformedKeystore(String),
+    #[error("unsupported keystore: {0}")]
+    UnsupportedKeystore(String),
+    #[error("keystore io error at {path}: {source}")]
+    KeystoreIo {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error(transparent)]
+    Wallet(#[from] WalletError),
+}
+
+impl AccountError {
+    pub(crate) fn from_mnemonic_error(err: MnemonicError, word_count: usize) -> Self {
+        match err {
+            MnemonicError::WordlistError(WordlistError::InvalidWord(word)) => {
+                AccountError::InvalidMnemonicWord(word)
+            }
+            MnemonicError::InvalidPhrase(_) => AccountError::InvalidMnemonicChecksum,
+            MnemonicError::InvalidEntropyLength(_) | MnemonicError::InvalidWordCount(_) => {
+                AccountError::InvalidMnemonicLength(word_count)
+            }
+            err => AccountError::Wallet(WalletError::Bip39Error(err)),
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use std::path::Path;
+
+use aes::cipher::{KeyIvInit, StreamCipher};
+use aes::Aes128;
+use ethers::core::rand::{thread_rng, RngCore};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::utils::keccak256;
+use hmac::Hmac;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+use crate::account::Account;
+use crate::error::{AccountError, AccountResult};
+
+type Aes128Ctr = ctr::Ctr128BE<Aes128>;
+
+const KEYSTORE_VERSION: u8 = 3;
+const KEYSTORE_CIPHER: &str = "aes-128-ctr";
+const KEYSTORE_PRF: &str = "hmac-sha256";
+const DERIVED_KEY_LENGTH: u8 = 32;
+const SALT_LENGTH: usize = 32;
+const IV_LENGTH: usize = 16;
+
+/// Upper bounds of the kdf params of a keystore, so a crafted file cannot exhaust the memory
+/// or keep the CPU busy. scrypt takes `128 * r * n` bytes, geth's standard n = 2^18 fits.
+const MAX_SCRYPT_MEMORY: u64 = 256 * 1024 * 1024;
+const MAX_SCRYPT_P: u32 = 16;
+const MAX_PBKDF2_ROUNDS: u32 = 10_000_000;
+
+/// The key derivation function used to encrypt a keystore.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeystoreKdf {
+    /// scrypt with `n = 2^log_n`, `r = 8` and `p = 1`.
+    Scrypt { log_n: u8 },
+    /// pbkdf2 with hmac-sha256 and `c` rounds.
+    Pbkdf2 { rounds: u32 },
+}
+
+impl Default for KeystoreKdf {
+    fn default() -> Self {
+        // n = 4096 like geth's `--lightkdf`, fast to unlock for hot keys.
+        KeystoreKdf::Scrypt { log_n: 12 }
+    }
+}
+
+/// Web3 Secret Storage V3 keystore.
+/// https://ethereum.org/en/developers/docs/data-structures-and-encoding/web3-secret-storage/
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Keystore {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    pub crypto: KeystoreCrypto,
+    pub id: String,
+    pub version: u8,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeystoreCrypto {
+    pub cipher: String,
+    pub cipherparams: KeystoreCipherParams,
+    pub ciphertext: String,
+    pub kdf: String,
+    pub kdfparams: KeystoreKdfParams,
+    pub mac: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeystoreCipherParams {
+    pub iv: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KeystoreKdfParams {
+    Pbkdf2 {
+        c: u32,
+        dklen: u8,
+        prf: String,
+        salt: String,
+    },
+    Scrypt {
+        dklen: u8,
+        n: u32,
+        p: u32,
+        r: u32,
+        salt: String,
+    },
+}
+
+impl Keystore {
+    /// Encrypt a raw private key with the password.
+    pub fn encrypt(private_key: &[u8], password: &str, kdf: KeystoreKdf) -> AccountResult<Self> {
+        let mut rng = thread_rng();
+        let mut salt = vec![0u8; SALT_LENGTH];
+        rng.fill_bytes(&mut salt);
+        let mut iv = vec![0u8; IV_LENGTH];
+        rng.fill_bytes(&mut iv);
+
+        let (kdf_name, kdfparams) = match kdf {
+            KeystoreKdf::Scrypt { log_n } => (
+                "scrypt",
+                KeystoreKdfParams::Scrypt {
+                    dklen: DERIVED_KEY_LENGTH,
+                    n: 1u32.checked_shl(log_n as u32).ok_or_else(|| {
+                        AccountError::UnsupportedKeystore(format!("scrypt log_n {}", log_n))
+                    })?,
+                    p: 1,
+                    r: 8,
+                    salt: hex::encode(&salt),
+                },
+            ),
+            KeystoreKdf::Pbkdf2 { rounds } => (
+                "pbkdf2",
+                KeystoreKdfParams::Pbkdf2 {
+                    c: rounds,
+                    dklen: DERIVED_KEY_LENGTH,
+                    prf: KEYSTORE_PRF.to_string(),
+                    salt: hex::encode(&salt),
+                },
+            ),
+        };
+
+        let derived_key = derive_key(password, &kdfparams)?;
+
+        let mut ciphertext = private_key.to_vec();
+        Aes128Ctr::new_from_slices(&derived_key[..16], &iv)
+            .map_err(|e| AccountError::MalformedKeystore(e.to_string()))?
+            .apply_keystream(&mut ciphertext);
+
+        let mac = keystore_mac(&derived_key, &ciphertext);
+        let address = LocalWallet::from_bytes(private_key)
+            .map_err(|_| AccountError::InvalidPrivateKey)?
+            .address();
+
+        Ok(Keystore {
+            address: Some(hex::encode(address)),
+            crypto: KeystoreCrypto {
+                cipher: KEYSTORE_CIPHER.to_string(),
+                cipherparams: KeystoreCipherParams {
+                    iv: hex::encode(iv),
+                },
+                ciphertext: hex::encode(ciphertext),
+                kdf: kdf_name.to_string(),
+                kdfparams,
+                mac: hex::encode(mac),
+            },
+            id: uuid::Uuid::new_v4().to_string(),
+            version: KEYSTORE_VERSION,
+        })
+    }
+
+    /// Decrypt the raw private key with the password, it is wiped from memory when dropped.
+    pub fn decrypt(&self, password: &str) -> AccountResult<Zeroizing<Vec<u8>>> {
+        if self.version != KEYSTORE_VERSION {
+            return Err(AccountError::UnsupportedKeystore(format!(
+                "version {}",
+                self.version
+            )));
+        }
+        if self.crypto.cipher != KEYSTORE_CIPHER {
+            return Err(AccountError::UnsupportedKeystore(format!(
+                "cipher {}",
+                self.crypto.cipher
+            )));
+        }
+
+        let derived_key = derive_key(password, &self.crypto.kdfparams)?;
+        if derived_key.len() < 32 {
+            return Err(AccountError::MalformedKeystore(
+                "derived key length must be at least 32".to_string(),
+            ));
+        }
+
+        let ciphertext = decode_hex_field("ciphertext", &self.crypto.ciphertext)?;
+        let mac = decode_hex_field("mac", &self.crypto.mac)?;
+        if !bool::from(keystore_mac(&derived_key, &ciphertext).ct_eq(&mac)) {
+            return Err(AccountError::InvalidKeystorePassword);
+        }
+
+        let iv = decode_hex_field("iv", &self.crypto.cipherparams.iv)?;
+        let mut plaintext = Zeroizing::new(ciphertext);
+        Aes128Ctr::new_from_slices(&derived_key[..16], &iv)
+            .map_err(|e| AccountError::MalformedKeystore(format!("iv: {}", e)))?
+            .apply_keystream(&mut plaintext);
+
+        Ok(plaintext)
+    }
+}
+
+impl Account {
+    /// Load an account from a V3 keystore file.
+    pub fn from_keystore<P: AsRef<Path>>(path: P, password: &str) -> AccountResult<Self> {
+        let path = path.as_ref();
+        let keystore_json =
+            std::fs::read_to_string(path).map_err(|source| AccountError::KeystoreIo {
+                path: path.to_path_buf(),
+                source,
+            })?;
+
+        Self::from_keystore_json(&keystore_json, password)
+    }
+
+    /// Load an account from the content of a V3 keystore file.
+    pub fn from_keystore_json(keystore_json: &str, password: &str) -> AccountResult<Self> {
+        let keystore: Keystore = serde_json::from_str(keystore_json)
+            .map_err(|e| AccountError::MalformedKeystore(e.to_string()))?;
+        let private_key = keystore.decrypt(password)?;
+        let wallet = LocalWallet::from_bytes(&private_key)
+            .map_err(|_| AccountError::MalformedKeystore("invalid private key".to_string()))?;
+
+        Ok(Account::from_signer(wallet))
+    }
+
+    /// Encrypt the account into the content of a V3 keystore file.
+    pub fn to_keystore_json(&self, password: &str, kdf: KeystoreKdf) -> AccountResult<String> {
+        let keystore = Keystore::encrypt(&self.signer.signer().to_bytes(), password, kdf)?;
+        serde_json::to_string(&keystore).map_err(|e| AccountError::MalformedKeystore(e.to_string()))
+    }
+
+    /// Encrypt the account into a V3 keystore file at `path`.
+    pub fn write_keystore<P: AsRef<Path>>(
+        &self,
+        path: P,
+        password: &str,
+        kdf: KeystoreKdf,
+    ) -> AccountResult<()> {
+        let path = path.as_ref();
+        let keystore_json = self.to_keystore_json(password, kdf)?;
+        std::fs::write(path, keystore_json).map_err(|source| AccountError::KeystoreIo {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Ok(())
+    }
+}
+
+fn derive_key(password: &str, kdfparams: &KeystoreKdfParams) -> AccountResult<Zeroizing<Vec<u8>>> {
+    let key = match kdfparams {
+        KeystoreKdfParams::Pbkdf2 {
+            c,
+            dklen,
+            prf,
+            salt,
+        } => {
+            if prf != KEYSTORE_PRF {
+                return Err(AccountError::UnsupportedKeystore(format!("prf {}", prf)));
+            }
+            if *c > MAX_PBKDF2_ROUNDS {
+                return Err(AccountError::UnsupportedKeystore(format!("pbkdf2 c {}", c)));
+            }
+            let salt = decode_hex_field("salt", salt)?;
+            let mut key = Zeroizing::new(vec![0u8; *dklen as usize]);
+            pbkdf2::pbkdf2::<Hmac<Sha256>>(password.as_bytes(), &salt, *c, &mut key);
+            key
+        }
+        KeystoreKdfParams::Scrypt {
+            dklen,
+            n,
+            p,
+            r,
+            salt,
+        } => {
+            if !n.is_power_of_two() {
+                return Err(AccountError::MalformedKeystore(
+                    "scrypt n must be a power of 2".to_string(),
+                ));
+            }
+            // Overflowing params are over the cap too.
+            let memory = 128u64
+                .checked_mul(*r as u64)
+                .and_then(|memory| memory.checked_mul(*n as u64))
+                .filter(|memory| *memory <= MAX_SCRYPT_MEMORY);
+            if memory.is_none() || *p > MAX_SCRYPT_P {
+                return Err(AccountError::UnsupportedKeystore(format!(
+                    "scrypt n {} r {} p {}",
+                    n, r, p
+                )));
+            }
+            let salt = decode_hex_field("salt", salt)?;
+            let params = scrypt::Params::new(n.trailing_zeros() as u8, *r, *p)
+                .map_err(|e| AccountError::MalformedKeystore(format!("scrypt params: {}", e)))?;
+            let mut key = Zeroizing::new(vec![0u8; *dklen as usize]);
+            scrypt::scrypt(password.as_bytes(), &salt, &params, &mut key)
+                .map_err(|e| AccountError::MalformedKeystore(format!("scrypt params: {}", e)))?;
+            key
+        }
+    };
+
+    Ok(key)
+}
+
+fn decode_hex_field(name: &str, value: &str) -> AccountResult<Vec<u8>> {
+    hex::decode(value).map_err(|e| AccountError::MalformedKeystore(format!("{}: {}", name, e)))
+}
+
+fn keystore_mac(derived_key: &[u8], ciphertext: &[u8]) -> [u8; 32] {
+    keccak256(Zeroizing::new([&derived_key[16..32], ciphertext].concat()))
+}
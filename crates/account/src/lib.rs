@@ -1,8 +1,20 @@
 pub mod account;
+pub mod error;
+pub mod keystore;
 pub mod nonce;
+pub mod nonce_manager;
+pub mod nonce_registry;
+pub mod signer;
+pub mod typed_data;
+pub mod verify;
 
 pub use account::Account;
+pub use error::{AccountError, AccountResult};
+pub use keystore::KeystoreKdf;
 pub use nonce::AccountNonce;
+pub use nonce_manager::{NonceManager, NonceProvider};
+pub use nonce_registry::NonceRegistry;
+pub use signer::{AccountSigner, RemoteSigner};
 
 #[cfg(test)]
 mod tests;
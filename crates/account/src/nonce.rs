@@ -1,7 +1,9 @@
-use ethers::{core::types::U256, types::Address};
+use ethers::types::Address;
 use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering::SeqCst;
-use std::{fmt::format, sync::Arc};
+#[cfg(test)]
+use std::sync::Arc;
+#[cfg(test)]
 use tokio::task::JoinSet;
 
 #[derive(Default, Debug)]
@@ -13,7 +15,7 @@ pub struct AccountNonce {
 impl AccountNonce {
     pub fn new(account_address: Address) -> Self {
         Self {
-            account_address: account_address,
+            account_address,
             ..Default::default()
         }
     }
@@ -25,6 +27,16 @@ impl AccountNonce {
     pub fn get_nonce(&self) -> u64 {
         self.nonce.load(SeqCst)
     }
+
+    /// Overwrite the nonce, unlike `update_nonce` it may move backwards.
+    pub fn set_nonce(&self, new_nonce: u64) {
+        self.nonce.store(new_nonce, SeqCst);
+    }
+
+    /// Return the current nonce and increase it by one.
+    pub fn fetch_next(&self) -> u64 {
+        self.nonce.fetch_add(1, SeqCst)
+    }
 }
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
@@ -37,7 +49,7 @@ async fn test_on_default_account_nonce() {
         task_set.spawn(async move { temp_account.update_nonce(i) });
     }
 
-    while let Some(_) = task_set.join_next().await {}
+    while task_set.join_next().await.is_some() {}
 
     assert_eq!(999, zero_account.get_nonce());
 }
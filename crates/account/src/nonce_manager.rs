@@ -0,0 +1,241 @@
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+
+use anyhow::Result;
+use ethers::types::Address;
+use serde::{Deserialize, Serialize};
+
+use crate::nonce::AccountNonce;
+
+/// Source of the on-chain pending nonce, implemented by the ethereum clients.
+#[allow(async_fn_in_trait)]
+pub trait NonceProvider {
+    async fn pending_nonce(&self, addr: Address) -> Result<u64>;
+}
+
+/// Persisted state of a `NonceManager`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NonceSnapshot {
+    pub address: Address,
+    pub next_nonce: u64,
+    pub gaps: Vec<u64>,
+}
+
+/// Hands out nonces of one account to concurrent senders.
+///
+/// The next nonce lives in an `AccountNonce`, nonces of dropped transactions are kept as gaps
+/// and handed out again before the counter moves on.
+#[derive(Debug)]
+pub struct NonceManager {
+    account_nonce: AccountNonce,
+    gaps: Mutex<BTreeSet<u64>>,
+}
+
+impl NonceManager {
+    pub fn new(account_address: Address) -> Self {
+        Self {
+            account_nonce: AccountNonce::new(account_address),
+            gaps: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    /// Create a manager seeded with the pending nonce on chain.
+    pub async fn new_synced<P: NonceProvider>(
+        account_address: Address,
+        provider: &P,
+    ) -> Result<Self> {
+        let manager = Self::new(account_address);
+        manager.sync(provider).await?;
+        Ok(manager)
+    }
+
+    pub fn account_address(&self) -> Address {
+        self.account_nonce.account_address
+    }
+
+    /// The nonce the counter will hand out next, ignoring gaps.
+    pub fn next_nonce(&self) -> u64 {
+        self.account_nonce.get_nonce()
+    }
+
+    /// Reserve a nonce, the lowest gap is filled first.
+    pub fn reserve(&self) -> u64 {
+        let mut gaps = self.gaps.lock().unwrap();
+        match gaps.pop_first() {
+            Some(nonce) => nonce,
+            None => self.account_nonce.fetch_next(),
+        }
+    }
+
+    /// Give back a reserved nonce whose transaction was never sent or got dropped.
+    pub fn release(&self, nonce: u64) {
+        let mut gaps = self.gaps.lock().unwrap();
+        let next_nonce = self.account_nonce.get_nonce();
+        if nonce >= next_nonce {
+            return;
+        }
+
+        if nonce + 1 == next_nonce {
+            // Shrink the counter instead of leaving a gap at the tail.
+            let mut next_nonce = nonce;
+            while next_nonce > 0 && gaps.remove(&(next_nonce - 1)) {
+                next_nonce -= 1;
+            }
+            self.account_nonce.set_nonce(next_nonce);
+            return;
+        }
+
+        gaps.insert(nonce);
+    }
+
+    /// Reset the manager to the given pending nonce, dropping all gaps.
+    pub fn reset(&self, pending_nonce: u64) {
+        let mut gaps = self.gaps.lock().unwrap();
+        gaps.clear();
+        self.account_nonce.set_nonce(pending_nonce);
+    }
+
+    pub fn snapshot(&self) -> NonceSnapshot {
+        let gaps = self.gaps.lock().unwrap();
+        NonceSnapshot {
+            address: self.account_nonce.account_address,
+            next_nonce: self.account_nonce.get_nonce(),
+            gaps: gaps.iter().copied().collect(),
+        }
+    }
+
+    pub fn from_snapshot(snapshot: &NonceSnapshot) -> Self {
+        let manager = Self::new(snapshot.address);
+        manager.account_nonce.set_nonce(snapshot.next_nonce);
+        manager.gaps.lock().unwrap().extend(
+            snapshot
+                .gaps
+                .iter()
+                .filter(|nonce| **nonce < snapshot.next_nonce),
+        );
+        manager
+    }
+
+    /// Fetch the pending nonce on chain and reset the manager to it.
+    pub async fn sync<P: NonceProvider>(&self, provider: &P) -> Result<u64> {
+        let pending_nonce = provider
+            .pending_nonce(self.account_nonce.account_address)
+            .await?;
+        self.reset(pending_nonce);
+        Ok(pending_nonce)
+    }
+
+    /// Resync with the chain if the send error is a nonce error.
+    /// Return whether the manager was resynced.
+    pub async fn resync_on_error<P: NonceProvider>(
+        &self,
+        provider: &P,
+        err: &anyhow::Error,
+    ) -> Result<bool> {
+        if !is_nonce_error(err) {
+            return Ok(false);
+        }
+
+        self.sync(provider).await?;
+        Ok(true)
+    }
+}
+
+/// Whether the node rejected the transaction because of its nonce.
+pub fn is_nonce_error(err: &anyhow::Error) -> bool {
+    let msg = format!("{:#}", err).to_lowercase();
+    msg.contains("nonce too low") || msg.contains("nonce too high")
+}
+
+#[cfg(test)]
+use {anyhow::anyhow, std::sync::Arc, tokio::task::JoinSet};
+
+#[cfg(test)]
+struct MockNonceProvider(u64);
+
+#[cfg(test)]
+impl NonceProvider for MockNonceProvider {
+    async fn pending_nonce(&self, _addr: Address) -> Result<u64> {
+        Ok(self.0)
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_on_concurrent_reserve() -> Result<()> {
+    let manager = Arc::new(NonceManager::new_synced(Address::zero(), &MockNonceProvider(7)).await?);
+
+    let mut task_set = JoinSet::new();
+    for _ in 0..1000 {
+        let temp_manager = Arc::clone(&manager);
+        task_set.spawn(async move { temp_manager.reserve() });
+    }
+
+    let mut nonces = vec![];
+    while let Some(nonce) = task_set.join_next().await {
+        nonces.push(nonce?);
+    }
+    nonces.sort();
+
+    assert_eq!((7..1007).collect::<Vec<u64>>(), nonces);
+    assert_eq!(1007, manager.next_nonce());
+
+    Ok(())
+}
+
+#[test]
+fn test_on_release_nonce() {
+    let manager = NonceManager::new(Address::zero());
+    manager.reset(10);
+
+    assert_eq!(10, manager.reserve());
+    assert_eq!(11, manager.reserve());
+    assert_eq!(12, manager.reserve());
+    assert_eq!(13, manager.reserve());
+
+    // Dropped tx in the middle leaves a gap which is filled first.
+    manager.release(11);
+    assert_eq!(11, manager.reserve());
+
+    // Releasing the tail moves the counter back, including gaps right below it.
+    manager.release(12);
+    manager.release(13);
+    assert_eq!(12, manager.next_nonce());
+    assert_eq!(12, manager.reserve());
+
+    // Nonces never handed out are ignored.
+    manager.release(100);
+    assert_eq!(13, manager.reserve());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_on_resync_on_nonce_error() -> Result<()> {
+    let manager = NonceManager::new(Address::zero());
+    manager.reset(3);
+    manager.reserve();
+    manager.reserve();
+    manager.release(3);
+
+    let resynced = manager
+        .resync_on_error(&MockNonceProvider(42), &anyhow!("insufficient funds"))
+        .await?;
+    assert!(!resynced);
+    assert_eq!(3, manager.reserve());
+
+    let resynced = manager
+        .resync_on_error(
+            &MockNonceProvider(42),
+            &anyhow!("(code: -32000, message: nonce too low, data: None)"),
+        )
+        .await?;
+    assert!(resynced);
+    assert_eq!(42, manager.reserve());
+
+    let resynced = manager
+        .resync_on_error(&MockNonceProvider(40), &anyhow!("Nonce too high"))
+        .await?;
+    assert!(resynced);
+    assert_eq!(40, manager.reserve());
+
+    Ok(())
+}
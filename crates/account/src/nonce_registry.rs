@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use anyhow::{anyhow, Context, Result};
+use ethers::types::Address;
+use futures::future::try_join_all;
+
+use crate::nonce_manager::{NonceManager, NonceProvider, NonceSnapshot};
+
+/// Nonce managers of many accounts keyed by address.
+///
+/// Entries are synced with the chain or restored from a snapshot when they are created, so a
+/// nonce is never handed out from a counter that did not start at the pending nonce.
+#[derive(Debug, Default)]
+pub struct NonceRegistry {
+    managers: RwLock<HashMap<Address, Arc<NonceManager>>>,
+}
+
+impl NonceRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, addr: Address) -> Option<Arc<NonceManager>> {
+        self.managers.read().unwrap().get(&addr).cloned()
+    }
+
+    /// Return the manager of the address, a missing one is created and synced with the chain.
+    pub async fn get_or_sync<P: NonceProvider>(
+        &self,
+        addr: Address,
+        provider: &P,
+    ) -> Result<Arc<NonceManager>> {
+        if let Some(manager) = self.get(addr) {
+            return Ok(manager);
+        }
+
+        let synced = NonceManager::new_synced(addr, provider).await?;
+        let mut managers = self.managers.write().unwrap();
+        // Another task may have inserted it while we were syncing, keep the first one.
+        Ok(Arc::clone(
+            managers.entry(addr).or_insert_with(|| Arc::new(synced)),
+        ))
+    }
+
+    /// Reserve a nonce of a registered address, see `get_or_sync` to register it.
+    pub fn reserve(&self, addr: Address) -> Result<u64> {
+        self.get(addr)
+            .map(|manager| manager.reserve())
+            .ok_or_else(|| anyhow!("nonce of {:?} not synced", addr))
+    }
+
+    pub fn release(&self, addr: Address, nonce: u64) {
+        if let Some(manager) = self.get(addr) {
+            manager.release(nonce);
+        }
+    }
+
+    pub fn addresses(&self) -> Vec<Address> {
+        self.managers.read().unwrap().keys().copied().collect()
+    }
+
+    /// Resync the given addresses with the chain concurrently, missing entries are created
+    /// once synced.
+    pub async fn sync_accounts<P: NonceProvider>(
+        &self,
+        addrs: &[Address],
+        provider: &P,
+    ) -> Result<()> {
+        try_join_all(addrs.iter().map(|addr| async move {
+            match self.get(*addr) {
+                Some(manager) => manager.sync(provider).await.map(|_| ()),
+                None => self.get_or_sync(*addr, provider).await.map(|_| ()),
+            }
+        }))
+        .await?;
+        Ok(())
+    }
+
+    /// Resync every registered address with the chain concurrently.
+    pub async fn sync_all<P: NonceProvider>(&self, provider: &P) -> Result<()> {
+        self.sync_accounts(&self.addresses(), provider).await
+    }
+
+    pub fn snapshot(&self) -> Vec<NonceSnapshot> {
+        let mut snapshots = self
+            .managers
+            .read()
+            .unwrap()
+            .values()
+            .map(|manager| manager.snapshot())
+            .collect::<Vec<_>>();
+        snapshots.sort_by_key(|snapshot| snapshot.address);
+        snapshots
+    }
+
+    pub fn from_snapshot(snapshots: &[NonceSnapshot]) -> Self {
+        let managers = snapshots
+            .iter()
+            .map(|snapshot| {
+                (
+                    snapshot.address,
+                    Arc::new(NonceManager::from_snapshot(snapshot)),
+                )
+            })
+            .collect();
+
+        Self {
+            managers: RwLock::new(managers),
+        }
+    }
+
+    /// Write the snapshot as JSON, through a temp file so a crash never leaves a partial file.
+    pub fn save_snapshot<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let snapshot_json = serde_json::to_string_pretty(&self.snapshot())?;
+
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        std::fs::write(&temp_path, snapshot_json)
+            .with_context(|| format!("failed to write nonce snapshot: {}", path.display()))?;
+        std::fs::rename(&temp_path, path)
+            .with_context(|| format!("failed to write nonce snapshot: {}", path.display()))?;
+
+        Ok(())
+    }
+
+    /// Restore a registry saved by `save_snapshot`. Nonces used elsewhere since the snapshot are
+    /// picked up by the resync after the first nonce too low error.
+    pub fn load_snapshot<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let snapshot_json = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read nonce snapshot: {}", path.display()))?;
+        let snapshots: Vec<NonceSnapshot> = serde_json::from_str(&snapshot_json)
+            .with_context(|| format!("malformed nonce snapshot: {}", path.display()))?;
+
+        Ok(Self::from_snapshot(&snapshots))
+    }
+}
+
+#[cfg(test)]
+use tokio::task::JoinSet;
+
+#[cfg(test)]
+struct MockNonceProvider;
+
+#[cfg(test)]
+impl NonceProvider for MockNonceProvider {
+    async fn pending_nonce(&self, addr: Address) -> Result<u64> {
+        Ok(addr.to_low_u64_be() * 100)
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_on_concurrent_registry_reserve() {
+    let registry = Arc::new(NonceRegistry::new());
+    let addrs = (0..10).map(Address::from_low_u64_be).collect::<Vec<_>>();
+    registry
+        .sync_accounts(&addrs, &MockNonceProvider)
+        .await
+        .unwrap();
+
+    let mut task_set = JoinSet::new();
+    for i in 0..1000 {
+        let temp_registry = Arc::clone(&registry);
+        let addr = Address::from_low_u64_be(i % 10);
+        task_set.spawn(async move { temp_registry.reserve(addr).unwrap() });
+    }
+
+    while task_set.join_next().await.is_some() {}
+
+    assert_eq!(10, registry.addresses().len());
+    for i in 0..10 {
+        assert_eq!(
+            i * 100 + 100,
+            registry
+                .get(Address::from_low_u64_be(i))
+                .unwrap()
+                .next_nonce()
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_on_registry_sync_and_snapshot() -> Result<()> {
+    let registry = NonceRegistry::new();
+    let addrs = (1..=3).map(Address::from_low_u64_be).collect::<Vec<_>>();
+
+    // An address never synced has no nonce to hand out.
+    assert!(registry.reserve(addrs[0]).is_err());
+
+    registry.sync_accounts(&addrs, &MockNonceProvider).await?;
+    assert_eq!(100, registry.reserve(addrs[0])?);
+    assert_eq!(101, registry.reserve(addrs[0])?);
+    assert_eq!(102, registry.reserve(addrs[0])?);
+    registry.release(addrs[0], 101);
+
+    let manager = registry
+        .get_or_sync(Address::from_low_u64_be(4), &MockNonceProvider)
+        .await?;
+    assert_eq!(400, manager.next_nonce());
+
+    let path = std::env::temp_dir().join(format!("nonce-snapshot-{}.json", std::process::id()));
+    registry.save_snapshot(&path)?;
+    let restored = NonceRegistry::load_snapshot(&path)?;
+    std::fs::remove_file(&path)?;
+
+    assert_eq!(registry.snapshot(), restored.snapshot());
+    assert_eq!(101, restored.reserve(addrs[0])?);
+    assert_eq!(103, restored.reserve(addrs[0])?);
+
+    restored.sync_all(&MockNonceProvider).await?;
+    assert_eq!(100, restored.reserve(addrs[0])?);
+
+    Ok(())
+}
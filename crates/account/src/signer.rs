@@ -0,0 +1,167 @@
+use anyhow::{anyhow, Context, Result};
+use ethers::prelude::k256::ecdsa::SigningKey;
+use ethers::prelude::transaction::eip2718::TypedTransaction;
+use ethers::prelude::Signature;
+use ethers::signers::{Signer, Wallet};
+use ethers::types::transaction::eip712::{Eip712, TypedData};
+use ethers::types::{Address, Bytes};
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+/// Signs on behalf of an `Account`, the key may live in memory or in a remote signing service.
+#[allow(async_fn_in_trait)]
+pub trait AccountSigner {
+    fn address(&self) -> Address;
+
+    /// EIP-191 personal sign.
+    async fn sign_message(&self, msg: &[u8]) -> Result<Signature>;
+
+    /// Sign the transaction and return its rlp encoded signed bytes.
+    async fn sign_transaction(&self, tx: &TypedTransaction) -> Result<Bytes>;
+
+    /// EIP-712 sign a struct deriving `Eip712`.
+    async fn sign_typed_data<T: Eip712 + Send + Sync>(&self, payload: &T) -> Result<Signature>;
+
+    /// EIP-712 sign a JSON typed data document.
+    async fn sign_typed_data_json(&self, typed_data: &TypedData) -> Result<Signature> {
+        self.sign_typed_data(typed_data).await
+    }
+}
+
+/// In memory key.
+impl AccountSigner for Wallet<SigningKey> {
+    fn address(&self) -> Address {
+        Signer::address(self)
+    }
+
+    async fn sign_message(&self, msg: &[u8]) -> Result<Signature> {
+        let res = Signer::sign_message(self, msg).await?;
+        Ok(res)
+    }
+
+    async fn sign_transaction(&self, tx: &TypedTransaction) -> Result<Bytes> {
+        let res = Signer::sign_transaction(self, tx).await?;
+        Ok(tx.rlp_signed(&res))
+    }
+
+    async fn sign_typed_data<T: Eip712 + Send + Sync>(&self, payload: &T) -> Result<Signature> {
+        let res = Signer::sign_typed_data(self, payload).await?;
+        Ok(res)
+    }
+}
+
+/// Signing service speaking JSON-RPC, e.g. Web3Signer or Clef.
+/// Transactions are signed by `eth_signTransaction`, messages by `eth_sign` and typed data by
+/// `eth_signTypedData_v4`.
+#[derive(Debug, Clone)]
+pub struct RemoteSigner {
+    client: Client,
+    endpoint: String,
+    address: Address,
+}
+
+impl RemoteSigner {
+    pub fn new(endpoint: String, address: Address) -> Self {
+        Self {
+            client: Client::new(),
+            endpoint,
+            address,
+        }
+    }
+
+    pub fn endpoint_info(&self) -> String {
+        format!("endpoint: {}", self.endpoint)
+    }
+
+    async fn request<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T> {
+        let req_body = json!({
+            "id": 1,
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+        });
+
+        let resp: Value = self
+            .client
+            .post(self.endpoint.as_str())
+            .json(&req_body)
+            .send()
+            .await
+            .with_context(|| format!("failed to request remote signer: {}", self.endpoint))?
+            .error_for_status()?
+            .json()
+            .await
+            .context("malformed remote signer response")?;
+
+        if let Some(err) = resp.get("error") {
+            return Err(anyhow!(
+                "remote signer error (code: {}, message: {})",
+                err["code"],
+                err["message"].as_str().unwrap_or_default()
+            ));
+        }
+
+        serde_json::from_value(resp["result"].clone()).context("malformed remote signer result")
+    }
+}
+
+impl AccountSigner for RemoteSigner {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    async fn sign_message(&self, msg: &[u8]) -> Result<Signature> {
+        let signature: String = self
+            .request("eth_sign", json!([self.address, Bytes::from(msg.to_vec())]))
+            .await?;
+        signature
+            .parse()
+            .map_err(|e| anyhow!("malformed remote signer signature: {}", e))
+    }
+
+    async fn sign_transaction(&self, tx: &TypedTransaction) -> Result<Bytes> {
+        let mut tx = tx.clone();
+        if tx.from().is_none() {
+            tx.set_from(self.address);
+        }
+
+        // ethers skips `chainId` when serializing, signers need it to pick the replay protection.
+        let mut tx_json = serde_json::to_value(&tx)?;
+        if let Some(chain_id) = tx.chain_id() {
+            tx_json["chainId"] = json!(chain_id);
+        }
+
+        // Web3Signer returns the raw transaction, Clef wraps it as `{"raw": .., "tx": ..}`.
+        let signed: Value = self
+            .request("eth_signTransaction", json!([tx_json]))
+            .await?;
+        let raw = match &signed {
+            Value::String(raw) => raw,
+            Value::Object(obj) => obj
+                .get("raw")
+                .and_then(Value::as_str)
+                .ok_or_else(|| anyhow!("malformed remote signer result: {}", signed))?,
+            _ => return Err(anyhow!("malformed remote signer result: {}", signed)),
+        };
+
+        raw.parse::<Bytes>()
+            .map_err(|e| anyhow!("malformed remote signer result: {}", e))
+    }
+
+    /// The signing service needs the whole JSON document, so only `TypedData` can be signed.
+    async fn sign_typed_data<T: Eip712 + Send + Sync>(&self, _payload: &T) -> Result<Signature> {
+        Err(anyhow!(
+            "remote signer only signs json typed data, use sign_typed_data_json"
+        ))
+    }
+
+    async fn sign_typed_data_json(&self, typed_data: &TypedData) -> Result<Signature> {
+        let signature: String = self
+            .request("eth_signTypedData_v4", json!([self.address, typed_data]))
+            .await?;
+        signature
+            .parse()
+            .map_err(|e| anyhow!("malformed remote signer signature: {}", e))
+    }
+}
@@ -5,7 +5,23 @@ use ethers_signers::{
 };
 
 use crate::account::{Account, KeyOpt};
+use crate::error::AccountError;
+use crate::keystore::KeystoreKdf;
+use crate::signer::RemoteSigner;
+use crate::typed_data::{parse_typed_data, recover_typed_data_signer, verify_typed_data};
+use crate::verify::{decode_signed_tx, recover_message_signer, recover_tx_signer};
 use anyhow::Result;
+use ethers::contract::{Eip712, EthAbiType};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::transaction::eip712::TypedData;
+use ethers::types::U256;
+use ethers::types::{Address, Bytes, Eip1559TransactionRequest, TransactionRequest};
+use ethers::utils::keccak256;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::task::JoinSet;
 
 #[test]
 fn test_new_account_from_private_key() {
@@ -21,7 +37,7 @@ fn test_new_account_from_private_key() {
     println!("Private key: {}", private_key);
 
     let actual_account_address = Account::new(KeyOpt {
-        private_key: private_key,
+        private_key,
         ..KeyOpt::default()
     })
     .unwrap()
@@ -60,3 +76,627 @@ fn test_new_account_from_phrase_key() -> Result<()> {
 
     Ok(())
 }
+
+// Well known BIP-39 test phrase used by hardhat and anvil.
+const TEST_PHRASE: &str = "test test test test test test test test test test test junk";
+
+#[test]
+fn test_new_account_from_phrase_key_with_account_index() -> Result<()> {
+    let expected_addresses = [
+        "0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266",
+        "0x70997970c51812dc3a010c7d01b50e0d17dc79c8",
+        "0x3c44cdddb6a900fa2b585dd299e03d12fa4293bc",
+    ];
+
+    for (index, expected_account_address) in expected_addresses.iter().enumerate() {
+        let account = Account::new(
+            KeyOpt::new_with_phrase_key(TEST_PHRASE.to_string()).with_account_index(index as u32),
+        )?;
+
+        assert_eq!(*expected_account_address, account.account_address()?);
+        assert_eq!(
+            Some(format!("m/44'/60'/0'/0/{}", index).as_str()),
+            account.derivation_path.as_deref()
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_new_account_from_phrase_key_with_derivation_path() -> Result<()> {
+    // BIP-44 vector of the "abandon ... about" phrase.
+    let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    let account = Account::new(
+        KeyOpt::new_with_phrase_key(phrase.to_string())
+            .with_derivation_path("m/44'/60'/0'/0/0".to_string()),
+    )?;
+    assert_eq!(
+        "0x9858effd232b4033e47d90003d41ec34ecaeda94",
+        account.account_address()?
+    );
+
+    let account = Account::new(
+        KeyOpt::new_with_phrase_key(TEST_PHRASE.to_string())
+            .with_derivation_path("m/44'/60'/0'/0".to_string())
+            .with_account_index(1),
+    )?;
+    assert_eq!(
+        "0x70997970c51812dc3a010c7d01b50e0d17dc79c8",
+        account.account_address()?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_new_batch_accounts_from_phrase_key() -> Result<()> {
+    let accounts = Account::new_batch(
+        KeyOpt::new_with_phrase_key(TEST_PHRASE.to_string()).with_account_indexes(0..4),
+    )?;
+
+    let actual_addresses = accounts
+        .iter()
+        .map(|account| account.account_address())
+        .collect::<Result<Vec<String>>>()?;
+
+    assert_eq!(
+        vec![
+            "0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266",
+            "0x70997970c51812dc3a010c7d01b50e0d17dc79c8",
+            "0x3c44cdddb6a900fa2b585dd299e03d12fa4293bc",
+            "0x90f79bf6eb2c4f870365e785982e1f101e93b906",
+        ],
+        actual_addresses
+    );
+
+    assert!(Account::new(
+        KeyOpt::new_with_phrase_key(TEST_PHRASE.to_string()).with_account_indexes(0..4)
+    )
+    .is_err());
+
+    assert!(Account::new_batch(
+        KeyOpt::new_with_phrase_key(TEST_PHRASE.to_string())
+            .with_account_index(1)
+            .with_account_indexes(0..4)
+    )
+    .is_err());
+
+    Ok(())
+}
+
+// Pbkdf2 test vector of the Web3 Secret Storage Definition.
+const PBKDF2_KEYSTORE: &str = r#"{
+    "crypto" : {
+        "cipher" : "aes-128-ctr",
+        "cipherparams" : {
+            "iv" : "6087dab2f9fdbbfaddc31a909735c1e6"
+        },
+        "ciphertext" : "5318b4d5bcd28de64ee5559e671353e16f075ecae9f99c7a79a38af5f869aa46",
+        "kdf" : "pbkdf2",
+        "kdfparams" : {
+            "c" : 262144,
+            "dklen" : 32,
+            "prf" : "hmac-sha256",
+            "salt" : "ae3cd4e7013836a3df6bd7241b12db061dbe2c6785853cce422d148a624ce0bd"
+        },
+        "mac" : "517ead924a9d0dc3124507e3393d175ce3ff7c1e96529c6c555ce9e51205e9b2"
+    },
+    "id" : "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+    "version" : 3
+}"#;
+
+const PBKDF2_KEYSTORE_PRIVATE_KEY: &str =
+    "0x7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d";
+
+#[test]
+fn test_new_account_from_keystore() -> Result<()> {
+    let expected_account_address = Account::new(KeyOpt::new_with_private_key(
+        PBKDF2_KEYSTORE_PRIVATE_KEY.to_string(),
+    ))?
+    .account_address()?;
+
+    let account = Account::from_keystore_json(PBKDF2_KEYSTORE, "testpassword")?;
+    assert_eq!(expected_account_address, account.account_address()?);
+
+    assert!(matches!(
+        Account::from_keystore_json(PBKDF2_KEYSTORE, "wrongpassword"),
+        Err(AccountError::InvalidKeystorePassword)
+    ));
+
+    assert!(matches!(
+        Account::from_keystore_json(r#"{"crypto": {}}"#, "testpassword"),
+        Err(AccountError::MalformedKeystore(_))
+    ));
+
+    assert!(matches!(
+        Account::from_keystore("/not/exist/keystore.json", "testpassword"),
+        Err(AccountError::KeystoreIo { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_keystore_kdf_params_are_capped() -> Result<()> {
+    let mut keystore: Value = serde_json::from_str(PBKDF2_KEYSTORE)?;
+    keystore["crypto"]["kdfparams"]["c"] = json!(u32::MAX);
+    assert!(matches!(
+        Account::from_keystore_json(&keystore.to_string(), "testpassword"),
+        Err(AccountError::UnsupportedKeystore(_))
+    ));
+
+    // 1GiB of memory.
+    keystore["crypto"]["kdf"] = json!("scrypt");
+    keystore["crypto"]["kdfparams"] = json!({
+        "dklen": 32,
+        "n": 1 << 20,
+        "p": 1,
+        "r": 8,
+        "salt": "ae3cd4e7013836a3df6bd7241b12db061dbe2c6785853cce422d148a624ce0bd",
+    });
+    assert!(matches!(
+        Account::from_keystore_json(&keystore.to_string(), "testpassword"),
+        Err(AccountError::UnsupportedKeystore(_))
+    ));
+
+    // 128 * r * n overflows u64, or wraps to 0.
+    for (n, r) in [(1u64 << 31, u32::MAX as u64), (1 << 31, 1 << 26)] {
+        keystore["crypto"]["kdfparams"]["n"] = json!(n);
+        keystore["crypto"]["kdfparams"]["r"] = json!(r);
+        assert!(matches!(
+            Account::from_keystore_json(&keystore.to_string(), "testpassword"),
+            Err(AccountError::UnsupportedKeystore(_))
+        ));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_keystore_round_trip() -> Result<()> {
+    let account = Account::new(KeyOpt::new_with_private_key(
+        PBKDF2_KEYSTORE_PRIVATE_KEY.to_string(),
+    ))?;
+
+    for kdf in [KeystoreKdf::default(), KeystoreKdf::Pbkdf2 { rounds: 1024 }] {
+        let keystore_json = account.to_keystore_json("password", kdf)?;
+        let decrypted = Account::from_keystore_json(&keystore_json, "password")?;
+        assert_eq!(account.address, decrypted.address);
+        assert!(Account::from_keystore_json(&keystore_json, "").is_err());
+    }
+
+    let path = std::env::temp_dir().join(format!("keystore-{}.json", account.account_address()?));
+    account.write_keystore(&path, "password", KeystoreKdf::default())?;
+    let decrypted = Account::from_keystore(&path, "password")?;
+    std::fs::remove_file(&path)?;
+    assert_eq!(account.address, decrypted.address);
+
+    Ok(())
+}
+
+/// Minimal JSON-RPC signing service backed by a local wallet, one request per connection.
+async fn spawn_mock_remote_signer(wallet: LocalWallet) -> Result<String> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let endpoint = format!("http://{}", listener.local_addr()?);
+
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let wallet = wallet.clone();
+            tokio::spawn(async move {
+                let mut buf = vec![];
+                let mut chunk = [0u8; 4096];
+                let body = loop {
+                    let n = stream.read(&mut chunk).await?;
+                    buf.extend_from_slice(&chunk[..n]);
+                    let req = String::from_utf8_lossy(&buf).to_string();
+                    if let Some((head, body)) = req.split_once("\r\n\r\n") {
+                        let content_length = head
+                            .lines()
+                            .find_map(|line| {
+                                let (name, value) = line.split_once(':')?;
+                                name.eq_ignore_ascii_case("content-length")
+                                    .then(|| value.trim().parse::<usize>().ok())?
+                            })
+                            .unwrap_or_default();
+                        if body.len() >= content_length {
+                            break body.to_string();
+                        }
+                    }
+                    if n == 0 {
+                        return Err(anyhow::anyhow!("connection closed"));
+                    }
+                };
+
+                let req: Value = serde_json::from_str(&body)?;
+                let params = &req["params"];
+                let resp = match req["method"].as_str() {
+                    Some("eth_signTransaction") => {
+                        let tx: TypedTransaction = serde_json::from_value(params[0].clone())?;
+                        if tx.from() != Some(&Signer::address(&wallet)) {
+                            json!({"jsonrpc": "2.0", "id": req["id"], "error": {"code": -32000, "message": "unknown account"}})
+                        } else {
+                            let signature = wallet.sign_transaction(&tx).await?;
+                            json!({"jsonrpc": "2.0", "id": req["id"], "result": tx.rlp_signed(&signature)})
+                        }
+                    }
+                    Some("eth_signTypedData_v4") => {
+                        let typed_data: TypedData = serde_json::from_value(params[1].clone())?;
+                        let signature = wallet.sign_typed_data(&typed_data).await?;
+                        json!({"jsonrpc": "2.0", "id": req["id"], "result": format!("0x{}", signature)})
+                    }
+                    Some("eth_sign") => {
+                        let msg: Bytes = serde_json::from_value(params[1].clone())?;
+                        let signature = wallet.sign_message(msg.as_ref()).await?;
+                        json!({"jsonrpc": "2.0", "id": req["id"], "result": format!("0x{}", signature)})
+                    }
+                    _ => {
+                        json!({"jsonrpc": "2.0", "id": req["id"], "error": {"code": -32601, "message": "method not found"}})
+                    }
+                };
+
+                let resp = resp.to_string();
+                let http_resp = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    resp.len(),
+                    resp
+                );
+                stream.write_all(http_resp.as_bytes()).await?;
+                anyhow::Ok(())
+            });
+        }
+    });
+
+    Ok(endpoint)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_sign_with_remote_signer() -> Result<()> {
+    let local_account = Account::new(KeyOpt::new_with_phrase_key(TEST_PHRASE.to_string()))?;
+    let wallet = (*local_account.signer).clone();
+    let endpoint = spawn_mock_remote_signer(wallet.clone()).await?;
+
+    let remote_account = Account::from_signer(RemoteSigner::new(
+        endpoint.clone(),
+        Signer::address(&wallet),
+    ));
+    assert_eq!(
+        local_account.account_address()?,
+        remote_account.account_address()?
+    );
+
+    let tx: TypedTransaction = Eip1559TransactionRequest::new()
+        .to(Address::from_low_u64_be(1))
+        .value(1_000_000_000u64)
+        .nonce(7u64)
+        .gas(21_000u64)
+        .max_fee_per_gas(30_000_000_000u64)
+        .max_priority_fee_per_gas(1_000_000_000u64)
+        .chain_id(1u64)
+        .into();
+
+    let expected_tx_bytes = local_account.sign_tx(&tx).await?;
+    let actual_tx_bytes = remote_account.sign_tx(&tx).await?;
+    assert_eq!(expected_tx_bytes, actual_tx_bytes);
+
+    let expected_signature = local_account.sign_message("hello").await?;
+    let actual_signature = remote_account.sign_message("hello").await?;
+    assert_eq!(expected_signature, actual_signature);
+
+    let err = Account::from_signer(RemoteSigner::new(endpoint, Address::zero()))
+        .sign_tx(&tx)
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(
+        "remote signer error (code: -32000, message: unknown account)",
+        err.to_string()
+    );
+
+    Ok(())
+}
+
+// Mail example of the EIP-712 specification.
+const MAIL_TYPED_DATA: &str = r#"{
+    "types": {
+        "EIP712Domain": [
+            {"name": "name", "type": "string"},
+            {"name": "version", "type": "string"},
+            {"name": "chainId", "type": "uint256"},
+            {"name": "verifyingContract", "type": "address"}
+        ],
+        "Person": [
+            {"name": "name", "type": "string"},
+            {"name": "wallet", "type": "address"}
+        ],
+        "Mail": [
+            {"name": "from", "type": "Person"},
+            {"name": "to", "type": "Person"},
+            {"name": "contents", "type": "string"}
+        ]
+    },
+    "primaryType": "Mail",
+    "domain": {
+        "name": "Ether Mail",
+        "version": "1",
+        "chainId": 1,
+        "verifyingContract": "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC"
+    },
+    "message": {
+        "from": {"name": "Cow", "wallet": "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826"},
+        "to": {"name": "Bob", "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB"},
+        "contents": "Hello, Bob!"
+    }
+}"#;
+
+#[derive(Eip712, EthAbiType, Clone, Debug)]
+#[eip712(
+    name = "Permit Token",
+    version = "1",
+    chain_id = 1,
+    verifying_contract = "0x0000000000000000000000000000000000000001"
+)]
+struct Permit {
+    owner: Address,
+    spender: Address,
+    value: U256,
+    nonce: U256,
+    deadline: U256,
+}
+
+const PERMIT_TYPED_DATA: &str = r#"{
+    "types": {
+        "EIP712Domain": [
+            {"name": "name", "type": "string"},
+            {"name": "version", "type": "string"},
+            {"name": "chainId", "type": "uint256"},
+            {"name": "verifyingContract", "type": "address"}
+        ],
+        "Permit": [
+            {"name": "owner", "type": "address"},
+            {"name": "spender", "type": "address"},
+            {"name": "value", "type": "uint256"},
+            {"name": "nonce", "type": "uint256"},
+            {"name": "deadline", "type": "uint256"}
+        ]
+    },
+    "primaryType": "Permit",
+    "domain": {
+        "name": "Permit Token",
+        "version": "1",
+        "chainId": 1,
+        "verifyingContract": "0x0000000000000000000000000000000000000001"
+    },
+    "message": {
+        "owner": "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266",
+        "spender": "0x0000000000000000000000000000000000000002",
+        "value": "1000000",
+        "nonce": "0",
+        "deadline": "1700000000"
+    }
+}"#;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_sign_typed_data_json() -> Result<()> {
+    let private_key = format!("0x{}", hex::encode(keccak256("cow")));
+    let account = Account::new(KeyOpt::new_with_private_key(private_key))?;
+
+    let signature = account.sign_typed_data_json(MAIL_TYPED_DATA).await?;
+    assert_eq!(28, signature.v);
+    assert_eq!(
+        U256::from("0x4355c47d63924e8a72e509b65029052eb6c299d53a04e167c5775fd466751c9d"),
+        signature.r
+    );
+    assert_eq!(
+        U256::from("0x07299936d304c153f6443dfa05f40ff007d72911b6f72307f996231605b91562"),
+        signature.s
+    );
+
+    let typed_data = parse_typed_data(MAIL_TYPED_DATA)?;
+    assert_eq!(
+        account.address,
+        recover_typed_data_signer(&typed_data, &signature)?
+    );
+    assert!(!verify_typed_data(
+        &typed_data,
+        &signature,
+        Address::zero()
+    )?);
+
+    assert!(account
+        .sign_typed_data_json(r#"{"types": {}}"#)
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_sign_typed_data_struct() -> Result<()> {
+    let account = Account::new(KeyOpt::new_with_phrase_key(TEST_PHRASE.to_string()))?;
+    let permit = Permit {
+        owner: account.address,
+        spender: Address::from_low_u64_be(2),
+        value: U256::from(1_000_000u64),
+        nonce: U256::zero(),
+        deadline: U256::from(1_700_000_000u64),
+    };
+
+    let signature = account.sign_typed_data(&permit).await?;
+    assert!(verify_typed_data(&permit, &signature, account.address)?);
+
+    // The same permit as a JSON document signs to the same signature.
+    let json_signature = account.sign_typed_data_json(PERMIT_TYPED_DATA).await?;
+    assert_eq!(signature, json_signature);
+
+    let endpoint = spawn_mock_remote_signer((*account.signer).clone()).await?;
+    let remote_account = Account::from_signer(RemoteSigner::new(endpoint, account.address));
+    let remote_signature = remote_account
+        .sign_typed_data_json(PERMIT_TYPED_DATA)
+        .await?;
+    assert_eq!(signature, remote_signature);
+    assert!(remote_account.sign_typed_data(&permit).await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_verify_signatures() -> Result<()> {
+    let accounts = Account::new_batch(
+        KeyOpt::new_with_phrase_key(TEST_PHRASE.to_string()).with_account_indexes(0..2),
+    )?;
+    let (account, other_account) = (&accounts[0], &accounts[1]);
+
+    let signature = account.sign_message("hello").await?;
+    assert_eq!(
+        account.address,
+        recover_message_signer("hello", &signature)?
+    );
+    assert!(account.verify("hello", &signature)?);
+    assert!(!account.verify("hello!", &signature)?);
+    assert!(!other_account.verify("hello", &signature)?);
+
+    let legacy_tx: TypedTransaction = TransactionRequest::new()
+        .to(Address::from_low_u64_be(1))
+        .value(1u64)
+        .nonce(1u64)
+        .gas(21_000u64)
+        .gas_price(1_000_000_000u64)
+        .chain_id(1u64)
+        .into();
+    let eip1559_tx: TypedTransaction = Eip1559TransactionRequest::new()
+        .to(Address::from_low_u64_be(1))
+        .value(1u64)
+        .nonce(2u64)
+        .gas(21_000u64)
+        .max_fee_per_gas(30_000_000_000u64)
+        .max_priority_fee_per_gas(1_000_000_000u64)
+        .chain_id(1u64)
+        .into();
+
+    for tx in [legacy_tx, eip1559_tx] {
+        let raw_tx = account.sign_tx(&tx).await?;
+        assert_eq!(account.address, recover_tx_signer(&raw_tx)?);
+        assert!(account.verify_tx(&raw_tx)?);
+        assert!(!other_account.verify_tx(&raw_tx)?);
+
+        let (decoded_tx, _, _) = decode_signed_tx(&raw_tx)?;
+        assert_eq!(tx.nonce(), decoded_tx.nonce());
+    }
+
+    assert!(recover_tx_signer(&Bytes::from(vec![0x02, 0x01])).is_err());
+
+    let typed_data = parse_typed_data(PERMIT_TYPED_DATA)?;
+    let signature = account.sign_typed_data(&typed_data).await?;
+    assert!(account.verify_typed_data(&typed_data, &signature)?);
+    assert!(!other_account.verify_typed_data(&typed_data, &signature)?);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_sign_with_shared_account() -> Result<()> {
+    let account = Account::new(KeyOpt::new_with_phrase_key(TEST_PHRASE.to_string()))?;
+
+    let mut task_set = JoinSet::new();
+    for nonce in 0..100u64 {
+        let temp_account = account.clone();
+        task_set.spawn(async move {
+            let tx: TypedTransaction = Eip1559TransactionRequest::new()
+                .to(Address::from_low_u64_be(1))
+                .nonce(nonce)
+                .gas(21_000u64)
+                .max_fee_per_gas(30_000_000_000u64)
+                .max_priority_fee_per_gas(1_000_000_000u64)
+                .chain_id(1u64)
+                .into();
+            temp_account.sign_tx(&tx).await
+        });
+    }
+
+    while let Some(raw_tx) = task_set.join_next().await {
+        assert!(account.verify_tx(&raw_tx??)?);
+    }
+
+    // All clones share the same signer.
+    let clone = account.clone();
+    assert!(Arc::ptr_eq(&account.signer, &clone.signer));
+
+    Ok(())
+}
+
+#[test]
+fn test_new_account_errors() {
+    let new_account = |key: KeyOpt| Account::new(key).err().unwrap();
+
+    assert!(matches!(
+        new_account(KeyOpt::default()),
+        AccountError::MissingKey
+    ));
+    assert!(matches!(
+        new_account(KeyOpt {
+            private_key: PBKDF2_KEYSTORE_PRIVATE_KEY.to_string(),
+            phrase_key: TEST_PHRASE.to_string(),
+            ..KeyOpt::default()
+        }),
+        AccountError::AmbiguousKey
+    ));
+    assert!(matches!(
+        new_account(KeyOpt::new_with_private_key("0xzz".to_string())),
+        AccountError::InvalidHex(_)
+    ));
+    assert!(matches!(
+        new_account(KeyOpt::new_with_private_key("0x1234".to_string())),
+        AccountError::InvalidKeyLength {
+            expected: 32,
+            actual: 2
+        }
+    ));
+    assert!(matches!(
+        new_account(KeyOpt::new_with_private_key(format!(
+            "0x{}",
+            "00".repeat(32)
+        ))),
+        AccountError::InvalidPrivateKey
+    ));
+    assert!(matches!(
+        new_account(KeyOpt::new_with_phrase_key(
+            "test test test test test test test test test test test jnuk".to_string()
+        )),
+        AccountError::InvalidMnemonicWord(word) if word == "jnuk"
+    ));
+    assert!(matches!(
+        new_account(KeyOpt::new_with_phrase_key(
+            "test test test test test test test test test test test test".to_string()
+        )),
+        AccountError::InvalidMnemonicChecksum
+    ));
+    assert!(matches!(
+        new_account(KeyOpt::new_with_phrase_key(
+            "test test test test test test test test test test junk".to_string()
+        )),
+        AccountError::InvalidMnemonicLength(11)
+    ));
+    assert!(matches!(
+        new_account(
+            KeyOpt::new_with_phrase_key(TEST_PHRASE.to_string())
+                .with_derivation_path("m/44'/60'/x".to_string())
+        ),
+        AccountError::InvalidDerivationPath(_)
+    ));
+    assert!(matches!(
+        new_account(
+            KeyOpt::new_with_phrase_key(TEST_PHRASE.to_string()).with_account_indexes(0..2)
+        ),
+        AccountError::UnexpectedAccountIndexes
+    ));
+    assert!(matches!(
+        Account::new_batch(
+            KeyOpt::new_with_phrase_key(TEST_PHRASE.to_string())
+                .with_account_index(1)
+                .with_account_indexes(0..2)
+        ),
+        Err(AccountError::AmbiguousAccountIndex)
+    ));
+}
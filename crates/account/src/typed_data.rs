@@ -0,0 +1,32 @@
+use anyhow::{anyhow, Context, Result};
+use ethers::prelude::Signature;
+use ethers::types::transaction::eip712::{Eip712, TypedData};
+use ethers::types::{Address, H256};
+
+/// EIP-712 digest of a struct deriving `Eip712` or of a `TypedData` JSON document.
+pub fn typed_data_hash<T: Eip712>(payload: &T) -> Result<H256> {
+    let encoded = payload
+        .encode_eip712()
+        .map_err(|e| anyhow!("failed to encode eip712 payload: {}", e))?;
+    Ok(H256::from(encoded))
+}
+
+/// Parse an `eth_signTypedData_v4` JSON document.
+pub fn parse_typed_data(typed_data_json: &str) -> Result<TypedData> {
+    serde_json::from_str(typed_data_json).context("malformed eip712 typed data")
+}
+
+/// Recover the address which signed the EIP-712 payload.
+pub fn recover_typed_data_signer<T: Eip712>(payload: &T, signature: &Signature) -> Result<Address> {
+    let address = signature.recover(typed_data_hash(payload)?)?;
+    Ok(address)
+}
+
+/// Check the EIP-712 payload was signed by `address`.
+pub fn verify_typed_data<T: Eip712>(
+    payload: &T,
+    signature: &Signature,
+    address: Address,
+) -> Result<bool> {
+    Ok(recover_typed_data_signer(payload, signature)? == address)
+}
@@ -0,0 +1,41 @@
+use anyhow::{anyhow, Result};
+use ethers::prelude::Signature;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{Address, Bytes};
+use ethers::utils::rlp::Rlp;
+
+pub use crate::typed_data::{recover_typed_data_signer, verify_typed_data};
+
+/// Recover the address which personal signed (EIP-191) the message.
+pub fn recover_message_signer<M: AsRef<[u8]>>(msg: M, signature: &Signature) -> Result<Address> {
+    let address = signature.recover(msg.as_ref())?;
+    Ok(address)
+}
+
+/// Check the message was personal signed (EIP-191) by `address`.
+pub fn verify_message<M: AsRef<[u8]>>(
+    msg: M,
+    signature: &Signature,
+    address: Address,
+) -> Result<bool> {
+    Ok(recover_message_signer(msg, signature)? == address)
+}
+
+/// Decode a signed raw transaction, as returned by `Account::sign_tx`, and recover its sender.
+pub fn decode_signed_tx(raw_tx: &Bytes) -> Result<(TypedTransaction, Signature, Address)> {
+    let (tx, signature) = TypedTransaction::decode_signed(&Rlp::new(raw_tx.as_ref()))
+        .map_err(|e| anyhow!("malformed signed transaction: {}", e))?;
+    let address = signature.recover(tx.sighash())?;
+    Ok((tx, signature, address))
+}
+
+/// Recover the sender of a signed raw transaction.
+pub fn recover_tx_signer(raw_tx: &Bytes) -> Result<Address> {
+    let (_, _, address) = decode_signed_tx(raw_tx)?;
+    Ok(address)
+}
+
+/// Check the signed raw transaction was signed by `address`.
+pub fn verify_tx(raw_tx: &Bytes, address: Address) -> Result<bool> {
+    Ok(recover_tx_signer(raw_tx)? == address)
+}
@@ -0,0 +1,230 @@
+use std::{future::Future, time::Duration};
+
+use anyhow::Result;
+use tokio::{task::JoinSet, time::Instant};
+
+#[cfg(test)]
+use futures::future::BoxFuture;
+
+/// When a broadcast to several endpoints is considered done.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BroadcastPolicy {
+    /// Wait for every endpoint to answer.
+    #[default]
+    All,
+    /// Return as soon as one endpoint accepted the tx.
+    FirstSuccess,
+    /// Return as soon as N endpoints accepted the tx.
+    Quorum(usize),
+}
+
+impl BroadcastPolicy {
+    fn is_reached(&self, accepted_count: usize) -> bool {
+        match self {
+            BroadcastPolicy::All => false,
+            BroadcastPolicy::FirstSuccess => accepted_count >= 1,
+            BroadcastPolicy::Quorum(quorum) => accepted_count >= *quorum,
+        }
+    }
+}
+
+/// The answer of a single endpoint.
+#[derive(Debug, Clone)]
+pub struct EndpointResult {
+    pub endpoint: String,
+    /// The tx hash returned by the endpoint, or the error message.
+    pub result: std::result::Result<String, String>,
+    pub latency: Duration,
+}
+
+impl EndpointResult {
+    pub fn is_accepted(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+/// The outcome of broadcasting a tx to several endpoints.
+///
+/// With `FirstSuccess` and `Quorum` the endpoints still in flight once the policy is reached
+/// keep sending in the background and are missing from `results`.
+#[derive(Debug, Clone)]
+pub struct BroadcastReport {
+    pub policy: BroadcastPolicy,
+    /// Known before sending for signed txs, otherwise the hash of the first endpoint that accepted.
+    pub tx_hash: Option<String>,
+    /// Results in the order the endpoints answered.
+    pub results: Vec<EndpointResult>,
+    pub first_accepted: Option<String>,
+}
+
+impl BroadcastReport {
+    pub fn new(policy: BroadcastPolicy, tx_hash: Option<String>) -> Self {
+        Self {
+            policy,
+            tx_hash,
+            results: vec![],
+            first_accepted: None,
+        }
+    }
+
+    pub fn accepted_count(&self) -> usize {
+        self.results.iter().filter(|r| r.is_accepted()).count()
+    }
+
+    /// At least one endpoint accepted the tx, or the quorum was reached.
+    pub fn is_success(&self) -> bool {
+        match self.policy {
+            BroadcastPolicy::Quorum(quorum) => self.accepted_count() >= quorum,
+            _ => self.first_accepted.is_some(),
+        }
+    }
+
+    fn push(&mut self, endpoint_result: EndpointResult) {
+        if let std::result::Result::Ok(tx_hash) = &endpoint_result.result {
+            if self.first_accepted.is_none() {
+                self.first_accepted = Some(endpoint_result.endpoint.clone());
+            }
+            if self.tx_hash.is_none() {
+                self.tx_hash = Some(tx_hash.clone());
+            }
+        }
+        self.results.push(endpoint_result);
+    }
+}
+
+/// Run one send per endpoint concurrently and collect the results until the policy is reached.
+pub(crate) async fn broadcast<Fut>(
+    sends: impl IntoIterator<Item = (String, Fut)>,
+    policy: BroadcastPolicy,
+    tx_hash: Option<String>,
+) -> BroadcastReport
+where
+    Fut: Future<Output = Result<String>> + Send + 'static,
+{
+    let mut task_set = JoinSet::new();
+    for (endpoint, send) in sends {
+        task_set.spawn(async move {
+            let start = Instant::now();
+            // A send that panicked is reported as the error of its endpoint.
+            let result = match tokio::spawn(send).await {
+                std::result::Result::Ok(result) => result.map_err(|e| e.to_string()),
+                Err(e) => Err(format!("send task failed: {}", e)),
+            };
+            EndpointResult {
+                endpoint,
+                result,
+                latency: start.elapsed(),
+            }
+        });
+    }
+
+    let mut report = BroadcastReport::new(policy, tx_hash);
+    while let Some(joined) = task_set.join_next().await {
+        // The sends run on their own tasks, these only fail when the runtime shuts down.
+        let std::result::Result::Ok(endpoint_result) = joined else {
+            continue;
+        };
+        report.push(endpoint_result);
+        if policy.is_reached(report.accepted_count()) {
+            break;
+        }
+    }
+    task_set.detach_all();
+
+    report
+}
+
+/// A send answering after `delay_ms`. The tests run on a paused clock, so the sends answer
+/// in the order of their delays whatever the load of the machine.
+#[cfg(test)]
+fn delayed_send(
+    endpoint: &str,
+    delay_ms: u64,
+    result: std::result::Result<&str, &str>,
+) -> (String, impl Future<Output = Result<String>>) {
+    let result = result
+        .map(|tx_hash| tx_hash.to_string())
+        .map_err(|e| anyhow::anyhow!(e.to_string()));
+    (endpoint.to_string(), async move {
+        tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+        result
+    })
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_on_broadcast_all() {
+    let sends = vec![
+        delayed_send("a", 120, Ok("0x01")),
+        delayed_send("b", 10, Err("nonce too low")),
+        delayed_send("c", 60, Ok("0x01")),
+    ];
+    let report = broadcast(sends, BroadcastPolicy::All, None).await;
+
+    assert_eq!(report.results.len(), 3);
+    assert_eq!(report.results[0].endpoint, "b");
+    assert_eq!(report.results[0].result, Err("nonce too low".to_string()));
+    assert!(report.results[0].latency >= Duration::from_millis(10));
+    assert_eq!(report.first_accepted.as_deref(), Some("c"));
+    assert_eq!(report.tx_hash.as_deref(), Some("0x01"));
+    assert_eq!(report.accepted_count(), 2);
+    assert!(report.is_success());
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_on_broadcast_first_success() {
+    let sends = vec![
+        delayed_send("a", 120, Ok("0x01")),
+        delayed_send("b", 10, Err("rate limited")),
+        delayed_send("c", 60, Ok("0x01")),
+    ];
+    let report = broadcast(
+        sends,
+        BroadcastPolicy::FirstSuccess,
+        Some("0x01".to_string()),
+    )
+    .await;
+
+    assert_eq!(report.results.len(), 2);
+    assert_eq!(report.first_accepted.as_deref(), Some("c"));
+    assert!(report.is_success());
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_on_broadcast_quorum() {
+    let sends = vec![
+        delayed_send("a", 120, Ok("0x01")),
+        delayed_send("b", 10, Err("rate limited")),
+        delayed_send("c", 60, Ok("0x01")),
+    ];
+    let report = broadcast(sends, BroadcastPolicy::Quorum(2), None).await;
+    assert_eq!(report.results.len(), 3);
+    assert!(report.is_success());
+
+    let sends = vec![
+        delayed_send("a", 10, Ok("0x01")),
+        delayed_send("b", 60, Err("rate limited")),
+    ];
+    let report = broadcast(sends, BroadcastPolicy::Quorum(2), None).await;
+    assert_eq!(report.accepted_count(), 1);
+    assert!(!report.is_success());
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_on_broadcast_reports_panicked_send() {
+    let (endpoint, send) = delayed_send("a", 10, Ok("0x01"));
+    let sends: Vec<(String, BoxFuture<'static, Result<String>>)> = vec![
+        (endpoint, Box::pin(send)),
+        ("b".to_string(), Box::pin(async { panic!("send panicked") })),
+    ];
+    let report = broadcast(sends, BroadcastPolicy::All, None).await;
+
+    assert_eq!(report.results.len(), 2);
+    let panicked = &report.results[0];
+    assert_eq!(panicked.endpoint, "b");
+    assert!(panicked
+        .result
+        .as_ref()
+        .unwrap_err()
+        .contains("send panicked"));
+    assert!(report.is_success());
+}
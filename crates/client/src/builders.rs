@@ -1,4 +1,5 @@
 use anyhow::{anyhow, Ok, Result};
+use std::fmt;
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
@@ -35,33 +36,35 @@ pub enum BlockBuilderEndpoint {
     // All,
 }
 
-impl ToString for BlockBuilderEndpoint {
-    fn to_string(&self) -> String {
-        match self {
-            BlockBuilderEndpoint::Flashbots => "flashbots".to_string(),
-            BlockBuilderEndpoint::BeaverBuild => "beaverbuild".to_string(),
-            BlockBuilderEndpoint::Rsync => "rsync".to_string(),
-            BlockBuilderEndpoint::Builder0x69 => "0x69".to_string(),
-            BlockBuilderEndpoint::GambitLabs => "gambitlabs".to_string(),
-            BlockBuilderEndpoint::EthBuilder => "ethbuilder".to_string(),
-            BlockBuilderEndpoint::Titan => "titan".to_string(),
-            BlockBuilderEndpoint::BuildAI => "buildai".to_string(),
-            BlockBuilderEndpoint::Payload => "payload".to_string(),
-            BlockBuilderEndpoint::Lightspeed => "lightspeed".to_string(),
-            BlockBuilderEndpoint::NFactorial => "nfactorial".to_string(),
-            BlockBuilderEndpoint::BobaBuilder => "bobabuilder".to_string(),
-            BlockBuilderEndpoint::F1b => "f1b".to_string(),
-            BlockBuilderEndpoint::JetBldr => "jetbldr".to_string(),
-            BlockBuilderEndpoint::PenguinBuild => "penguinbuild".to_string(),
-            BlockBuilderEndpoint::LokiBuild => "loki".to_string(),
-            BlockBuilderEndpoint::EdenNetwork => "edennetwork".to_string(),
-            BlockBuilderEndpoint::TBuilder => "tbuilder".to_string(),
-            BlockBuilderEndpoint::Eigenphi => "eigenphi".to_string(),
-            BlockBuilderEndpoint::BlockBleelder => "blockbleelder".to_string(),
-            BlockBuilderEndpoint::ManifoldFinance => "manifoldfinance".to_string(),
-            BlockBuilderEndpoint::Pandabuild => "pandabuild".to_string(),
-            BlockBuilderEndpoint::SmithBot => "smithbot".to_string(),
-        }
+impl fmt::Display for BlockBuilderEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            BlockBuilderEndpoint::Flashbots => "flashbots",
+            BlockBuilderEndpoint::BeaverBuild => "beaverbuild",
+            BlockBuilderEndpoint::Rsync => "rsync",
+            BlockBuilderEndpoint::Builder0x69 => "0x69",
+            BlockBuilderEndpoint::GambitLabs => "gambitlabs",
+            BlockBuilderEndpoint::EthBuilder => "ethbuilder",
+            BlockBuilderEndpoint::Titan => "titan",
+            BlockBuilderEndpoint::BuildAI => "buildai",
+            BlockBuilderEndpoint::Payload => "payload",
+            BlockBuilderEndpoint::Lightspeed => "lightspeed",
+            BlockBuilderEndpoint::NFactorial => "nfactorial",
+            BlockBuilderEndpoint::BobaBuilder => "bobabuilder",
+            BlockBuilderEndpoint::F1b => "f1b",
+            BlockBuilderEndpoint::JetBldr => "jetbldr",
+            BlockBuilderEndpoint::PenguinBuild => "penguinbuild",
+            BlockBuilderEndpoint::LokiBuild => "loki",
+            BlockBuilderEndpoint::EdenNetwork => "edennetwork",
+            BlockBuilderEndpoint::TBuilder => "tbuilder",
+            BlockBuilderEndpoint::Eigenphi => "eigenphi",
+            BlockBuilderEndpoint::BlockBleelder => "blockbleelder",
+            BlockBuilderEndpoint::ManifoldFinance => "manifoldfinance",
+            BlockBuilderEndpoint::Pandabuild => "pandabuild",
+            BlockBuilderEndpoint::SmithBot => "smithbot",
+        };
+
+        f.write_str(name)
     }
 }
 
@@ -111,7 +114,7 @@ impl BlockBuilderEndpoint {
         };
 
         if endpoint.eq("not supported") {
-            return Err(anyhow!("{} not support for goerli", self.to_string()));
+            return Err(anyhow!("{} not support for goerli", self));
         }
 
         Ok(endpoint)
@@ -124,17 +127,78 @@ impl BlockBuilderEndpoint {
         };
 
         if endpoint.eq("not supported") {
-            return Err(anyhow!("{} not support for sepolia", self.to_string()));
+            return Err(anyhow!("{} not support for sepolia", self));
         }
 
         Ok(endpoint)
     }
+
+    pub fn holesky_testnet_endpoint(&self) -> Result<String> {
+        let endpoint = match self {
+            BlockBuilderEndpoint::Flashbots => "https://relay-holesky.flashbots.net".to_string(),
+            _ => "not supported".to_string(),
+        };
+
+        if endpoint.eq("not supported") {
+            return Err(anyhow!("{} not support for holesky", self));
+        }
+
+        Ok(endpoint)
+    }
+
+    /// The endpoint of the builder on `network`, an error if the builder does not serve it.
+    pub fn endpoint(&self, network: &Network) -> Result<String> {
+        match network {
+            Network::Mainnet => self.mainnet_endpoint(),
+            Network::Goerli => self.goerli_testnet_endpoint(),
+            Network::Sepolia => self.sepolia_testnet_endpoint(),
+            Network::Holesky => self.holesky_testnet_endpoint(),
+            Network::Custom {
+                builder_endpoints, ..
+            } => builder_endpoints
+                .iter()
+                .find(|(builder, _)| builder == self)
+                .map(|(_, endpoint)| endpoint.clone())
+                .ok_or_else(|| anyhow!("{} not support for {}", self, network)),
+        }
+    }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Network {
     Mainnet,
     Goerli,
     Sepolia,
+    Holesky,
+    /// A devnet or a network the builders list is not known for, with the endpoints to use.
+    Custom {
+        chain_id: u64,
+        builder_endpoints: Vec<(BlockBuilderEndpoint, String)>,
+    },
+}
+
+impl Network {
+    pub fn chain_id(&self) -> u64 {
+        match self {
+            Network::Mainnet => 1,
+            Network::Goerli => 5,
+            Network::Sepolia => 11155111,
+            Network::Holesky => 17000,
+            Network::Custom { chain_id, .. } => *chain_id,
+        }
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Network::Mainnet => f.write_str("mainnet"),
+            Network::Goerli => f.write_str("goerli"),
+            Network::Sepolia => f.write_str("sepolia"),
+            Network::Holesky => f.write_str("holesky"),
+            Network::Custom { chain_id, .. } => write!(f, "chain {}", chain_id),
+        }
+    }
 }
 
 pub fn all_block_builder_endpoints(network: Network) -> Vec<String> {
@@ -142,17 +206,9 @@ pub fn all_block_builder_endpoints(network: Network) -> Vec<String> {
     let mut endpoints = vec![];
 
     for builder in BlockBuilderEndpoint::iter() {
-        let builder_endpoint = match network {
-            Network::Mainnet => builder
-                .mainnet_endpoint()
-                .map_or("".to_string(), |endpoint| endpoint),
-            Network::Goerli => builder
-                .goerli_testnet_endpoint()
-                .map_or("".to_string(), |endpoint| endpoint),
-            Network::Sepolia => builder
-                .sepolia_testnet_endpoint()
-                .map_or("".to_string(), |endpoint| endpoint),
-        };
+        let builder_endpoint = builder
+            .endpoint(&network)
+            .map_or("".to_string(), |endpoint| endpoint);
 
         if builder_endpoint.is_empty() {
             continue;
@@ -187,3 +243,28 @@ fn test_on_sepolia_testnet_endpoints() {
     );
     println!("{:?}", all_block_builder_endpoints(Network::Sepolia));
 }
+
+#[test]
+fn test_on_network_endpoints() {
+    assert_eq!(
+        all_block_builder_endpoints(Network::Holesky),
+        vec!["https://relay-holesky.flashbots.net".to_string()]
+    );
+    assert!(BlockBuilderEndpoint::Titan
+        .endpoint(&Network::Sepolia)
+        .is_err());
+
+    let devnet = Network::Custom {
+        chain_id: 1337,
+        builder_endpoints: vec![(
+            BlockBuilderEndpoint::Flashbots,
+            "http://127.0.0.1:18545".to_string(),
+        )],
+    };
+    assert_eq!(devnet.chain_id(), 1337);
+    assert_eq!(
+        BlockBuilderEndpoint::Flashbots.endpoint(&devnet).unwrap(),
+        "http://127.0.0.1:18545"
+    );
+    assert!(BlockBuilderEndpoint::Titan.endpoint(&devnet).is_err());
+}
@@ -1,29 +1,234 @@
-use std::fmt::format;
-
-use crate::{builders::BlockBuilderEndpoint, json_rpc};
-use anyhow::{anyhow, Error};
-use ethers::{
-    middleware::builder,
-    types::transaction::{eip2718::TypedTransaction, response},
+use crate::{
+    builders::{BlockBuilderEndpoint, Network},
+    json_rpc,
 };
-use futures::{
-    future::{join_all, try_join_all},
-    stream::FuturesUnordered,
-    try_join,
+use account::{Account, AccountSigner};
+use anyhow::anyhow;
+use ethers::{
+    prelude::k256::ecdsa::SigningKey,
+    signers::Wallet,
+    types::{Address, BlockNumber, Bytes, H256, U256},
+    utils::keccak256,
 };
-use reqwest::{header, Client, Response, StatusCode};
-use serde::{Deserialize, Serialize};
-use serde_json::{error, Value};
+use futures::{stream::FuturesUnordered, StreamExt};
+use reqwest::{header, Client, StatusCode};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
+use serde_json::Value;
 use serde_with::skip_serializing_none;
-use tokio::{
-    io::join,
-    task::{self, JoinSet},
+use std::{str::FromStr, time::Duration};
+use thiserror::Error;
+use tokio::{task::JoinHandle, time::Instant};
+use tracing::{error, info};
+
+#[cfg(test)]
+use {
+    crate::mock_node::{MockNode, MockReply},
+    account::account::KeyOpt,
+    ethers::types::Signature,
+    serde_json::json,
 };
-use tracing::{error, info, warn};
 
-#[derive(Default)]
-pub struct BundleClient {
+/// The params of `eth_callBundle`.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CallBundleParams {
+    pub txs: Vec<String>,
+    pub block_number: String,
+    pub state_block_number: BlockNumber,
+    pub timestamp: Option<u64>,
+    pub base_fee: Option<u64>,
+}
+
+/// What `eth_callBundle` simulates the bundle on, the next block on top of latest by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallBundleOverrides {
+    /// The block whose state the bundle runs on.
+    pub state_block: BlockNumber,
+    pub timestamp: Option<u64>,
+    pub base_fee: Option<u64>,
+}
+
+impl Default for CallBundleOverrides {
+    fn default() -> Self {
+        Self {
+            state_block: BlockNumber::Latest,
+            timestamp: None,
+            base_fee: None,
+        }
+    }
+}
+
+/// The simulation of a tx of the bundle.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallBundleTxResult {
+    pub tx_hash: H256,
+    pub from_address: Address,
+    /// `None` for contract creations.
+    #[serde(default)]
+    pub to_address: Option<Address>,
+    pub gas_used: u64,
+    #[serde(deserialize_with = "deserialize_u256")]
+    pub gas_price: U256,
+    #[serde(deserialize_with = "deserialize_u256")]
+    pub gas_fees: U256,
+    /// What the tx paid to the coinbase, gas fees included.
+    #[serde(deserialize_with = "deserialize_u256")]
+    pub coinbase_diff: U256,
+    #[serde(deserialize_with = "deserialize_u256")]
+    pub eth_sent_to_coinbase: U256,
+    /// The return data of a successful tx.
+    #[serde(default)]
+    pub value: Option<Bytes>,
+    #[serde(default)]
+    pub error: Option<String>,
+    /// The revert reason of a reverted tx.
+    #[serde(default)]
+    pub revert: Option<String>,
+}
+
+impl CallBundleTxResult {
+    pub fn is_success(&self) -> bool {
+        self.error.is_none() && self.revert.is_none()
+    }
+}
+
+/// The simulation of a bundle by `eth_callBundle`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallBundleResponse {
+    pub bundle_hash: H256,
+    /// The coinbase diff over the gas used, the price builders rank bundles by.
+    #[serde(deserialize_with = "deserialize_u256")]
+    pub bundle_gas_price: U256,
+    #[serde(deserialize_with = "deserialize_u256")]
+    pub coinbase_diff: U256,
+    #[serde(deserialize_with = "deserialize_u256")]
+    pub eth_sent_to_coinbase: U256,
+    #[serde(deserialize_with = "deserialize_u256")]
+    pub gas_fees: U256,
+    pub results: Vec<CallBundleTxResult>,
+    pub state_block_number: u64,
+    pub total_gas_used: u64,
+}
+
+impl CallBundleResponse {
+    pub fn is_success(&self) -> bool {
+        self.results.iter().all(CallBundleTxResult::is_success)
+    }
+
+    /// The first tx that reverted or failed.
+    pub fn first_failure(&self) -> Option<&CallBundleTxResult> {
+        self.results.iter().find(|result| !result.is_success())
+    }
+}
+
+/// Why a builder did not accept a bundle.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum BundleError {
+    #[error("request failed: {0}")]
+    Request(String),
+    #[error("http status {status}: {body}")]
+    Http { status: u16, body: String },
+    #[error("json-rpc error {code}: {message}")]
+    JsonRpc { code: i64, message: String },
+    #[error("invalid response: {0}")]
+    InvalidResponse(String),
+    /// The task posting to the builder panicked or was cancelled.
+    #[error("task failed: {0}")]
+    Task(String),
+}
+
+/// The answer of a single builder.
+#[derive(Debug, Clone)]
+pub struct BuilderResult {
+    /// `None` for relays given by url.
+    pub builder: Option<BlockBuilderEndpoint>,
+    pub endpoint: String,
+    /// `None` when no response was received.
+    pub http_status: Option<u16>,
+    /// The bundle hash, when the builder returns one.
+    pub result: Result<Option<H256>, BundleError>,
+    pub latency: Duration,
+}
+
+impl BuilderResult {
+    pub fn is_accepted(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+/// The outcome of sending a bundle to several builders, in the order they answered.
+#[derive(Debug, Clone, Default)]
+pub struct BundleReport {
+    pub results: Vec<BuilderResult>,
+    /// The builders not serving the network of the client, the bundle was not sent to them.
+    pub skipped: Vec<BlockBuilderEndpoint>,
+}
+
+impl BundleReport {
+    pub fn accepted_count(&self) -> usize {
+        self.results.iter().filter(|r| r.is_accepted()).count()
+    }
+
+    /// At least one builder accepted the bundle.
+    pub fn is_success(&self) -> bool {
+        self.accepted_count() > 0
+    }
+
+    /// The first bundle hash returned, builders hash the same bundle alike.
+    pub fn bundle_hash(&self) -> Option<H256> {
+        self.results
+            .iter()
+            .find_map(|r| r.result.as_ref().ok().copied().flatten())
+    }
+
+    /// The builders to send the bundle to again.
+    pub fn failed_builders(&self) -> Vec<BlockBuilderEndpoint> {
+        self.results
+            .iter()
+            .filter(|r| !r.is_accepted())
+            .filter_map(|r| r.builder.clone())
+            .collect()
+    }
+}
+
+/// A bundle sent under a replacement uuid, builders keep only its latest version.
+#[derive(Debug, Clone)]
+pub struct ReplaceableBundle {
+    pub replacement_uuid: String,
+    pub target_block: u64,
+    /// The last submission, its results list every endpoint the bundle was sent to.
+    pub report: BundleReport,
+}
+
+impl ReplaceableBundle {
+    fn targets(&self) -> Vec<(Option<BlockBuilderEndpoint>, String)> {
+        self.report
+            .results
+            .iter()
+            .map(|r| (r.builder.clone(), r.endpoint.clone()))
+            .collect()
+    }
+}
+
+/// The params of `eth_cancelBundle`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelBundleParams {
+    pub replacement_uuid: String,
+}
+
+/// Header relays authenticate searchers by, see [`flashbots_signature`].
+pub const FLASHBOTS_SIGNATURE_HEADER: &str = "X-Flashbots-Signature";
+
+/// Sends bundles to the block builders of a network, signing the requests with the searcher
+/// identity if set.
+pub struct BundleClient<S = Wallet<SigningKey>> {
     client: Client,
+    network: Network,
+    searcher: Option<Account<S>>,
 }
 
 #[skip_serializing_none]
@@ -41,7 +246,13 @@ pub struct BundleParams {
 }
 
 impl BundleClient {
+    /// A client for mainnet builders.
     pub fn new() -> Self {
+        Self::for_network(Network::Mainnet)
+    }
+
+    /// A client sending to the endpoints of the builders on `network`.
+    pub fn for_network(network: Network) -> Self {
         let mut headers = header::HeaderMap::new();
         headers.insert(
             "Content-Type",
@@ -50,90 +261,380 @@ impl BundleClient {
 
         Self {
             client: Client::builder().default_headers(headers).build().unwrap(),
-            ..Default::default()
+            network,
+            searcher: None,
         }
     }
+}
 
-    /// return bundle hash if exist
+impl Default for BundleClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: AccountSigner> BundleClient<S> {
+    /// Sign every request with `searcher`, the identity relays build the searcher reputation on.
+    /// Keep it apart from the accounts signing the txs.
+    pub fn with_searcher<T: AccountSigner>(self, searcher: Account<T>) -> BundleClient<T> {
+        BundleClient {
+            client: self.client,
+            network: self.network,
+            searcher: Some(searcher),
+        }
+    }
+
+    pub fn network(&self) -> &Network {
+        &self.network
+    }
+
+    pub fn searcher_address(&self) -> Option<Address> {
+        self.searcher.as_ref().map(|searcher| searcher.address)
+    }
+
+    /// Send the bundle to the builders serving the network of the client, and report how each
+    /// answered. The other builders are skipped.
     pub async fn send_bundle(
         &self,
         raw_txns: Vec<String>,
         target_block: u64,
         builder_endpoints: Vec<BlockBuilderEndpoint>,
-    ) -> anyhow::Result<()> {
+    ) -> anyhow::Result<BundleReport> {
+        let params = BundleParams {
+            txs: raw_txns,
+            block_number: format!("{:#x}", target_block),
+            ..Default::default()
+        };
+        self.send_bundle_to_builders(params, builder_endpoints)
+            .await
+    }
+
+    /// Send the bundle to relays given by url, e.g. a private relay.
+    pub async fn send_bundle_to_urls(
+        &self,
+        raw_txns: Vec<String>,
+        target_block: u64,
+        urls: Vec<String>,
+    ) -> anyhow::Result<BundleReport> {
+        let params = BundleParams {
+            txs: raw_txns,
+            block_number: format!("{:#x}", target_block),
+            ..Default::default()
+        };
+        let targets = urls.into_iter().map(|url| (None, url)).collect();
+        self.send_bundle_to(params, targets).await
+    }
+
+    /// Send the bundle under a new replacement uuid, to replace or cancel it later.
+    pub async fn send_replaceable_bundle(
+        &self,
+        raw_txns: Vec<String>,
+        target_block: u64,
+        builder_endpoints: Vec<BlockBuilderEndpoint>,
+    ) -> anyhow::Result<ReplaceableBundle> {
+        let replacement_uuid = uuid::Uuid::new_v4().to_string();
+        let params = BundleParams {
+            txs: raw_txns,
+            block_number: format!("{:#x}", target_block),
+            replacement_uuid: Some(replacement_uuid.clone()),
+            ..Default::default()
+        };
+        let report = self
+            .send_bundle_to_builders(params, builder_endpoints)
+            .await?;
+
+        Ok(ReplaceableBundle {
+            replacement_uuid,
+            target_block,
+            report,
+        })
+    }
+
+    /// Replace the bundle with `raw_txns` on the endpoints it was sent to, e.g. to target the
+    /// next block. The builders drop the previous version.
+    pub async fn replace_bundle(
+        &self,
+        bundle: &ReplaceableBundle,
+        raw_txns: Vec<String>,
+        target_block: u64,
+    ) -> anyhow::Result<ReplaceableBundle> {
+        let params = BundleParams {
+            txs: raw_txns,
+            block_number: format!("{:#x}", target_block),
+            replacement_uuid: Some(bundle.replacement_uuid.clone()),
+            ..Default::default()
+        };
+        let mut report = self.send_bundle_to(params, bundle.targets()).await?;
+        report.skipped = bundle.report.skipped.clone();
+
+        Ok(ReplaceableBundle {
+            replacement_uuid: bundle.replacement_uuid.clone(),
+            target_block,
+            report,
+        })
+    }
+
+    /// Cancel the bundle with `eth_cancelBundle` on every endpoint it was sent to, including
+    /// the ones that failed to answer, as they may have received it anyway.
+    pub async fn cancel_bundle(&self, bundle: &ReplaceableBundle) -> anyhow::Result<BundleReport> {
+        let req_body = serde_json::to_string(&CancelBundleParams {
+            replacement_uuid: bundle.replacement_uuid.clone(),
+        })?;
+        let cancel_req = json_rpc::to_json_rpc_method("eth_cancelBundle", req_body);
+        self.post_to_all("cancel bundle", cancel_req, bundle.targets())
+            .await
+    }
+
+    /// Simulate the bundle with `eth_callBundle` on `builder` of the client network, e.g. to
+    /// check its profit before sending it.
+    pub async fn call_bundle(
+        &self,
+        raw_txns: Vec<String>,
+        target_block: u64,
+        overrides: CallBundleOverrides,
+        builder: BlockBuilderEndpoint,
+    ) -> anyhow::Result<CallBundleResponse> {
+        let url = builder.endpoint(&self.network)?;
+        self.call_bundle_at_url(raw_txns, target_block, overrides, url)
+            .await
+    }
+
+    /// Simulate the bundle with `eth_callBundle` on the relay at `url`.
+    pub async fn call_bundle_at_url(
+        &self,
+        raw_txns: Vec<String>,
+        target_block: u64,
+        overrides: CallBundleOverrides,
+        url: String,
+    ) -> anyhow::Result<CallBundleResponse> {
         if raw_txns.is_empty() {
-            error!("empty txns is not allowed");
             return Err(anyhow!("empty txns"));
         }
 
-        let req_body = serde_json::to_string_pretty(&BundleParams {
+        let req_body = serde_json::to_string(&CallBundleParams {
             txs: raw_txns,
             block_number: format!("{:#x}", target_block),
-            ..Default::default()
+            state_block_number: overrides.state_block,
+            timestamp: overrides.timestamp,
+            base_fee: overrides.base_fee,
         })?;
+        let call_req = json_rpc::to_json_rpc_method("eth_callBundle", req_body);
+        let signature = match &self.searcher {
+            Some(searcher) => Some(flashbots_signature(searcher, &call_req).await?),
+            None => None,
+        };
+
+        let (_, result) = post_json_rpc(&self.client, &url, call_req, signature).await;
+        let response = serde_json::from_value(result?)?;
+        Ok(response)
+    }
 
-        let mut tasks = JoinSet::new();
-
-        for endpoints in builder_endpoints.iter() {
-            for mainnet_url in endpoints.mainnet_endpoint() {
-                let cli = self.client.clone();
-                let bundle_req = json_rpc::to_json_rpc(req_body.clone());
-
-                let sub_task = async move {
-                    let bundle_req_copy = bundle_req.clone();
-                    let url: String = mainnet_url.clone();
-                    let resp = cli
-                        .post::<String>(mainnet_url)
-                        .body(bundle_req)
-                        .send()
-                        .await;
-
-                    match resp {
-                        Ok(res) => {
-                            if res.status() != StatusCode::OK {
-                                error!("failed to send bundle to endpoint: {}, bundle request param: {}", url, bundle_req_copy);
-                                return Err(anyhow!("failed to send bundle to endpoint"));
-                            }
-
-                            let res_str = match res.text().await {
-                                Ok(res_string) => res_string,
-                                Err(e) => {
-                                    error!("failed to convert resp text: {}", e);
-                                    return Err(anyhow!("failed to convert resp text"));
-                                }
-                            };
-
-                            let resp_json: Value = serde_json::from_str(res_str.as_str())?;
-                            if let Some(bundlehash) = resp_json["result"]["bundleHash"].as_str() {
-                                info!(
-                                    "send bundle to endpoint: {}, bundle hash: {}",
-                                    url, bundlehash
-                                );
-                            } else {
-                                info!("send bundle to endpoint: {}, resp: {}", url, resp_json);
-                            }
-
-                            Ok(())
-                        }
-                        Err(e) => {
-                            error!("failed to send bundle to endpoint: {}, bundle request param: {}, error: {}", url, bundle_req_copy, e.to_string());
-                            return Err(anyhow!("failed to send bundle to endpoint"));
-                        }
-                    }
-                };
-
-                tasks.spawn(sub_task);
+    async fn send_bundle_to_builders(
+        &self,
+        params: BundleParams,
+        builder_endpoints: Vec<BlockBuilderEndpoint>,
+    ) -> anyhow::Result<BundleReport> {
+        let mut targets = vec![];
+        let mut skipped = vec![];
+        for builder in builder_endpoints {
+            match builder.endpoint(&self.network) {
+                Ok(url) => targets.push((Some(builder), url)),
+                Err(_) => skipped.push(builder),
             }
         }
+        if targets.is_empty() {
+            return Err(anyhow!("none of the builders serve {}", self.network));
+        }
+        if !skipped.is_empty() {
+            info!("skip builders not serving {}: {:?}", self.network, skipped);
+        }
+
+        let mut report = self.send_bundle_to(params, targets).await?;
+        report.skipped = skipped;
+        Ok(report)
+    }
+
+    async fn send_bundle_to(
+        &self,
+        params: BundleParams,
+        targets: Vec<(Option<BlockBuilderEndpoint>, String)>,
+    ) -> anyhow::Result<BundleReport> {
+        if params.txs.is_empty() {
+            error!("empty txns is not allowed");
+            return Err(anyhow!("empty txns"));
+        }
+
+        let req_body = serde_json::to_string_pretty(&params)?;
+        self.post_to_all("send bundle", json_rpc::to_json_rpc(req_body), targets)
+            .await
+    }
 
-        while let Some(_) = tasks.join_next().await {}
-        Ok(())
+    /// Post the signed request to every target at once.
+    async fn post_to_all(
+        &self,
+        action: &'static str,
+        req: String,
+        targets: Vec<(Option<BlockBuilderEndpoint>, String)>,
+    ) -> anyhow::Result<BundleReport> {
+        let signature = match &self.searcher {
+            Some(searcher) => Some(flashbots_signature(searcher, &req).await?),
+            None => None,
+        };
+
+        let mut tasks = FuturesUnordered::new();
+        for (builder, url) in targets {
+            let cli = self.client.clone();
+            let req = req.clone();
+            let signature = signature.clone();
+            let endpoint = url.clone();
+
+            let started = Instant::now();
+            let task = tokio::spawn(async move {
+                let (http_status, result) = post_bundle(&cli, &url, req.clone(), signature).await;
+                match &result {
+                    Ok(bundle_hash) => info!(
+                        "{} to endpoint: {}, bundle hash: {:?}",
+                        action, url, bundle_hash
+                    ),
+                    Err(e) => error!(
+                        "failed to {} to endpoint: {}, request: {}, error: {}",
+                        action, url, req, e
+                    ),
+                }
+                (http_status, result)
+            });
+            tasks.push(join_builder_task(builder, endpoint, started, task));
+        }
+
+        let mut report = BundleReport::default();
+        while let Some(builder_result) = tasks.next().await {
+            report.results.push(builder_result);
+        }
+        Ok(report)
     }
 }
 
+/// The http status if any and the bundle hash if any.
+type PostResult = (Option<u16>, Result<Option<H256>, BundleError>);
+
+/// Wait for the post to a builder, a task that panicked is reported as the error of its builder.
+async fn join_builder_task(
+    builder: Option<BlockBuilderEndpoint>,
+    endpoint: String,
+    started: Instant,
+    task: JoinHandle<PostResult>,
+) -> BuilderResult {
+    let (http_status, result) = task.await.unwrap_or_else(|e| {
+        error!("task to endpoint: {} failed, error: {}", endpoint, e);
+        (None, Err(BundleError::Task(e.to_string())))
+    });
+    BuilderResult {
+        builder,
+        endpoint,
+        http_status,
+        result,
+        latency: started.elapsed(),
+    }
+}
+
+/// Post the JSON-RPC request, return the http status if any and the bundle hash if any.
+async fn post_bundle(
+    client: &Client,
+    url: &str,
+    bundle_req: String,
+    signature: Option<String>,
+) -> PostResult {
+    let (http_status, result) = post_json_rpc(client, url, bundle_req, signature).await;
+    let result = result.and_then(|result| match result["bundleHash"].as_str() {
+        Some(bundle_hash) => H256::from_str(bundle_hash)
+            .map(Some)
+            .map_err(|e| BundleError::InvalidResponse(format!("bundle hash: {}", e))),
+        // Some builders accept the bundle without returning its hash.
+        None => Ok(None),
+    });
+    (http_status, result)
+}
+
+/// Post the JSON-RPC request, return the http status if any and the `result` of the response.
+async fn post_json_rpc(
+    client: &Client,
+    url: &str,
+    req_body: String,
+    signature: Option<String>,
+) -> (Option<u16>, Result<Value, BundleError>) {
+    let mut req = client.post(url).body(req_body);
+    if let Some(signature) = signature {
+        req = req.header(FLASHBOTS_SIGNATURE_HEADER, signature);
+    }
+
+    let resp = match req.send().await {
+        Ok(resp) => resp,
+        Err(e) => return (None, Err(BundleError::Request(e.to_string()))),
+    };
+    let status = resp.status();
+    let http_status = Some(status.as_u16());
+    let body = match resp.text().await {
+        Ok(body) => body,
+        Err(e) => return (http_status, Err(BundleError::Request(e.to_string()))),
+    };
+
+    // Some builders answer JSON-RPC errors with an http error status.
+    let resp_json = serde_json::from_str::<Value>(&body).ok();
+    if let Some(error) = resp_json.as_ref().and_then(|resp| resp.get("error")) {
+        return (
+            http_status,
+            Err(BundleError::JsonRpc {
+                code: error["code"].as_i64().unwrap_or_default(),
+                message: error["message"].as_str().unwrap_or_default().to_string(),
+            }),
+        );
+    }
+    if status != StatusCode::OK {
+        return (
+            http_status,
+            Err(BundleError::Http {
+                status: status.as_u16(),
+                body,
+            }),
+        );
+    }
+    // A response without `result`, like `{}`, did not accept anything.
+    match resp_json {
+        Some(mut resp_json) if resp_json.get("result").is_some() => {
+            (http_status, Ok(resp_json["result"].take()))
+        }
+        _ => (http_status, Err(BundleError::InvalidResponse(body))),
+    }
+}
+
+/// Relays answer amounts in wei as decimal strings, accept hex strings and numbers too.
+fn deserialize_u256<'de, D: Deserializer<'de>>(deserializer: D) -> Result<U256, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::String(value) => match value.strip_prefix("0x") {
+            Some(hex) => U256::from_str_radix(hex, 16).map_err(D::Error::custom),
+            None => U256::from_dec_str(&value).map_err(D::Error::custom),
+        },
+        Value::Number(value) => value
+            .as_u64()
+            .map(U256::from)
+            .ok_or_else(|| D::Error::custom(format!("invalid amount {}", value))),
+        value => Err(D::Error::custom(format!("invalid amount {}", value))),
+    }
+}
+
+/// `<address>:<signature>`, the searcher EIP-191 signature over the hex keccak of the body.
+pub async fn flashbots_signature<S: AccountSigner>(
+    searcher: &Account<S>,
+    body: &str,
+) -> anyhow::Result<String> {
+    let body_hash = format!("{:?}", H256(keccak256(body)));
+    let signature = searcher.sign_message(&body_hash).await?;
+    Ok(format!("{:?}:0x{}", searcher.address, signature))
+}
+
 #[test]
 fn test_on_bundle_client() {
-    let cli = BundleClient::new();
+    let _cli = BundleClient::new();
 }
 
 #[test]
@@ -170,3 +671,342 @@ fn test_on_parse_bundle_params_json() {
     let request_params = json_rpc::to_json_rpc(raw_bundle_json);
     println!("{:?}", request_params);
 }
+
+#[tokio::test]
+async fn test_on_send_bundle_to_mock_relays() {
+    let relay = MockNode::start().await.unwrap();
+    let failing_relay = MockNode::start().await.unwrap();
+    failing_relay.on("eth_sendBundle", MockReply::Status(503));
+    let rejecting_relay = MockNode::start().await.unwrap();
+    rejecting_relay.on("eth_sendBundle", MockReply::error(-32000, "bundle too old"));
+
+    let raw_txns = vec!["0x02f871".to_string(), "0x02f872".to_string()];
+    let report = BundleClient::new()
+        .send_bundle_to_urls(
+            raw_txns.clone(),
+            0xa2740a,
+            vec![relay.url(), failing_relay.url(), rejecting_relay.url()],
+        )
+        .await
+        .unwrap();
+
+    relay.assert_called("eth_sendBundle", 1);
+    failing_relay.assert_called("eth_sendBundle", 1);
+    let params: BundleParams =
+        serde_json::from_value(relay.calls_to("eth_sendBundle")[0].params[0].clone()).unwrap();
+    assert_eq!(params.txs, raw_txns);
+    assert_eq!(params.block_number, "0xa2740a");
+
+    assert_eq!(report.results.len(), 3);
+    assert!(report.is_success());
+    assert_eq!(report.accepted_count(), 1);
+    assert!(report.bundle_hash().is_some());
+    let result_of = |url: String| {
+        report
+            .results
+            .iter()
+            .find(|r| r.endpoint == url)
+            .unwrap()
+            .clone()
+    };
+    let accepted = result_of(relay.url());
+    assert_eq!(accepted.http_status, Some(200));
+    assert_eq!(accepted.result, Ok(report.bundle_hash()));
+    let failed = result_of(failing_relay.url());
+    assert_eq!(failed.http_status, Some(503));
+    assert!(matches!(
+        failed.result,
+        Err(BundleError::Http { status: 503, .. })
+    ));
+    assert_eq!(
+        result_of(rejecting_relay.url()).result,
+        Err(BundleError::JsonRpc {
+            code: -32000,
+            message: "bundle too old".to_string(),
+        })
+    );
+    // Relays given by url are not builders to retry.
+    assert!(report.failed_builders().is_empty());
+
+    assert!(BundleClient::new()
+        .send_bundle_to_urls(vec![], 1, vec![relay.url()])
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_on_send_bundle_on_network() {
+    let relay = MockNode::start().await.unwrap();
+    let devnet = Network::Custom {
+        chain_id: 1337,
+        builder_endpoints: vec![(BlockBuilderEndpoint::Flashbots, relay.url())],
+    };
+    let client = BundleClient::for_network(devnet.clone());
+    assert_eq!(client.network(), &devnet);
+
+    let report = client
+        .send_bundle(
+            vec!["0x02f871".to_string()],
+            1,
+            vec![BlockBuilderEndpoint::Flashbots, BlockBuilderEndpoint::Titan],
+        )
+        .await
+        .unwrap();
+
+    relay.assert_called("eth_sendBundle", 1);
+    assert_eq!(report.results.len(), 1);
+    assert_eq!(
+        report.results[0].builder,
+        Some(BlockBuilderEndpoint::Flashbots)
+    );
+    assert!(report.is_success());
+    assert_eq!(report.skipped, vec![BlockBuilderEndpoint::Titan]);
+
+    // A relay answering neither a result nor an error did not take the bundle.
+    relay.push("eth_sendBundle", MockReply::Body(json!({})));
+    let report = client
+        .send_bundle(
+            vec!["0x02f871".to_string()],
+            1,
+            vec![BlockBuilderEndpoint::Flashbots],
+        )
+        .await
+        .unwrap();
+    assert!(matches!(
+        report.results[0].result,
+        Err(BundleError::InvalidResponse(_))
+    ));
+    assert_eq!(
+        report.failed_builders(),
+        vec![BlockBuilderEndpoint::Flashbots]
+    );
+
+    // Nothing is sent when no builder serves the network.
+    assert!(client
+        .send_bundle(
+            vec!["0x02f871".to_string()],
+            1,
+            vec![BlockBuilderEndpoint::Titan]
+        )
+        .await
+        .is_err());
+    relay.assert_called("eth_sendBundle", 2);
+}
+
+#[tokio::test]
+async fn test_on_call_bundle() {
+    let relay = MockNode::start().await.unwrap();
+    relay.on(
+        "eth_callBundle",
+        MockReply::result(serde_json::json!({
+            "bundleGasPrice": "476190476193",
+            "bundleHash": "0x73b1e258c7a42fd0230b2fd05529c5d4b6fcb66c227783f8bece8aeacdd1db2e",
+            "coinbaseDiff": "20000000000126000",
+            "ethSentToCoinbase": "20000000000000000",
+            "gasFees": "126000",
+            "results": [
+                {
+                    "coinbaseDiff": "10000000000063000",
+                    "ethSentToCoinbase": "10000000000000000",
+                    "fromAddress": "0x02a727155aef8609c9f7f2179b2a1f560b39f5a0",
+                    "gasFees": "63000",
+                    "gasPrice": "476190476193",
+                    "gasUsed": 21000,
+                    "toAddress": "0x73625f59cadc5009cb458b751b3e7b6b48c06f2c",
+                    "txHash": "0x669b4704a7d993a946cdd6e2f95233f308ce0c4649d2e04944e8299efcaa098a",
+                    "value": "0x"
+                },
+                {
+                    "coinbaseDiff": "10000000000063000",
+                    "ethSentToCoinbase": "10000000000000000",
+                    "fromAddress": "0x02a727155aef8609c9f7f2179b2a1f560b39f5a0",
+                    "gasFees": "63000",
+                    "gasPrice": "476190476193",
+                    "gasUsed": 21000,
+                    "toAddress": "0x73625f59cadc5009cb458b751b3e7b6b48c06f2c",
+                    "txHash": "0xa839ee83465657cac01adc1d50d96c1b586ed498120a84a64749c0034b4f19fa",
+                    "error": "execution reverted",
+                    "revert": "too late"
+                }
+            ],
+            "stateBlockNumber": 5221585,
+            "totalGasUsed": 42000
+        })),
+    );
+
+    let overrides = CallBundleOverrides {
+        timestamp: Some(1615920932),
+        base_fee: Some(1_000_000_000),
+        ..Default::default()
+    };
+    let response = BundleClient::new()
+        .call_bundle_at_url(
+            vec!["0x02f871".to_string()],
+            0x4fad92,
+            overrides,
+            relay.url(),
+        )
+        .await
+        .unwrap();
+
+    let params = &relay.calls_to("eth_callBundle")[0].params[0];
+    assert_eq!(params["blockNumber"], "0x4fad92");
+    assert_eq!(params["stateBlockNumber"], "latest");
+    assert_eq!(params["timestamp"], 1615920932);
+    assert_eq!(params["baseFee"], 1_000_000_000);
+
+    assert_eq!(response.total_gas_used, 42000);
+    assert_eq!(response.state_block_number, 5221585);
+    assert_eq!(
+        response.coinbase_diff,
+        U256::from_dec_str("20000000000126000").unwrap()
+    );
+    assert_eq!(response.bundle_gas_price, U256::from(476190476193u64));
+    assert!(response.results[0].is_success());
+    assert_eq!(response.results[0].value, Some(Bytes::new()));
+    assert!(!response.is_success());
+    let failure = response.first_failure().unwrap();
+    assert_eq!(failure.revert.as_deref(), Some("too late"));
+    assert_eq!(failure.tx_hash, response.results[1].tx_hash);
+
+    relay.on("eth_callBundle", MockReply::error(-32000, "nonce too low"));
+    let err = BundleClient::new()
+        .call_bundle_at_url(
+            vec!["0x02f871".to_string()],
+            1,
+            CallBundleOverrides::default(),
+            relay.url(),
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<BundleError>(),
+        Some(&BundleError::JsonRpc {
+            code: -32000,
+            message: "nonce too low".to_string(),
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_on_replace_and_cancel_bundle() {
+    let relay = MockNode::start().await.unwrap();
+    relay.on("eth_cancelBundle", MockReply::result(Value::Null));
+    let failing_relay = MockNode::start().await.unwrap();
+    failing_relay.on("eth_sendBundle", MockReply::Status(503));
+    failing_relay.on("eth_cancelBundle", MockReply::result(Value::Null));
+    let client = BundleClient::for_network(Network::Custom {
+        chain_id: 1337,
+        builder_endpoints: vec![
+            (BlockBuilderEndpoint::Flashbots, relay.url()),
+            (BlockBuilderEndpoint::Titan, failing_relay.url()),
+        ],
+    });
+
+    let bundle = client
+        .send_replaceable_bundle(
+            vec!["0x02f871".to_string()],
+            10,
+            vec![
+                BlockBuilderEndpoint::Flashbots,
+                BlockBuilderEndpoint::Titan,
+                BlockBuilderEndpoint::BeaverBuild,
+            ],
+        )
+        .await
+        .unwrap();
+    assert!(uuid::Uuid::parse_str(&bundle.replacement_uuid).is_ok());
+    assert_eq!(bundle.target_block, 10);
+    assert_eq!(
+        bundle.report.skipped,
+        vec![BlockBuilderEndpoint::BeaverBuild]
+    );
+
+    let replaced = client
+        .replace_bundle(&bundle, vec!["0x02f872".to_string()], 11)
+        .await
+        .unwrap();
+    assert_eq!(replaced.replacement_uuid, bundle.replacement_uuid);
+    assert_eq!(replaced.target_block, 11);
+    assert_eq!(replaced.report.results.len(), 2);
+
+    let sent = relay.calls_to("eth_sendBundle");
+    assert_eq!(sent.len(), 2);
+    for (call, (tx, block_number)) in sent.iter().zip([("0x02f871", "0xa"), ("0x02f872", "0xb")]) {
+        let params: BundleParams = serde_json::from_value(call.params[0].clone()).unwrap();
+        assert_eq!(params.txs, vec![tx.to_string()]);
+        assert_eq!(params.block_number, block_number);
+        assert_eq!(
+            params.replacement_uuid,
+            Some(bundle.replacement_uuid.clone())
+        );
+    }
+    failing_relay.assert_called("eth_sendBundle", 2);
+
+    // The cancel also goes to the builder that failed to answer.
+    let report = client.cancel_bundle(&replaced).await.unwrap();
+    assert_eq!(report.accepted_count(), 2);
+    for node in [&relay, &failing_relay] {
+        let cancels = node.calls_to("eth_cancelBundle");
+        assert_eq!(cancels.len(), 1);
+        assert_eq!(
+            cancels[0].params[0]["replacementUuid"],
+            bundle.replacement_uuid.as_str()
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_on_sign_bundle_requests() {
+    let relay = MockNode::start().await.unwrap();
+    let searcher = Account::new(KeyOpt::new_with_private_key(
+        "59c6995e998f97a5a0044966f0945389dc9e86dae88c7a8412f4603b6b78690d".to_string(),
+    ))
+    .unwrap();
+    let searcher_address = searcher.address;
+    let client = BundleClient::new().with_searcher(searcher);
+    assert_eq!(client.searcher_address(), Some(searcher_address));
+
+    client
+        .send_bundle_to_urls(vec!["0x02f871".to_string()], 1, vec![relay.url()])
+        .await
+        .unwrap();
+
+    let call = &relay.calls_to("eth_sendBundle")[0];
+    let header = &call.headers[&FLASHBOTS_SIGNATURE_HEADER.to_lowercase()];
+    let (address, signature) = header.split_once(':').unwrap();
+    assert_eq!(Address::from_str(address).unwrap(), searcher_address);
+
+    let body_hash = format!("{:?}", H256(keccak256(&call.body)));
+    let signature = Signature::from_str(signature).unwrap();
+    assert!(account::verify::verify_message(body_hash, &signature, searcher_address).unwrap());
+
+    // Without a searcher the requests are not signed.
+    BundleClient::new()
+        .send_bundle_to_urls(vec!["0x02f871".to_string()], 1, vec![relay.url()])
+        .await
+        .unwrap();
+    let call = &relay.calls_to("eth_sendBundle")[1];
+    assert!(!call
+        .headers
+        .contains_key(&FLASHBOTS_SIGNATURE_HEADER.to_lowercase()));
+}
+
+#[tokio::test]
+async fn test_on_builder_task_panic_is_reported() {
+    let task = tokio::spawn(async {
+        panic!("builder task panicked");
+        #[allow(unreachable_code)]
+        (None, Ok(None))
+    });
+    let result = join_builder_task(
+        Some(BlockBuilderEndpoint::Flashbots),
+        "https://relay.flashbots.net".to_string(),
+        Instant::now(),
+        task,
+    )
+    .await;
+
+    assert_eq!(result.builder, Some(BlockBuilderEndpoint::Flashbots));
+    assert!(matches!(result.result, Err(BundleError::Task(_))));
+}
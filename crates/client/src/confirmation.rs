@@ -0,0 +1,358 @@
+use std::{pin::Pin, time::Duration};
+
+use anyhow::{anyhow, Result};
+use ethers::types::{Address, BlockNumber, Bytes, TransactionReceipt, H256};
+use futures::{Stream, StreamExt};
+use tokio::time::Instant;
+
+use crate::ethereum_client::EthereumClientTrait;
+
+#[cfg(test)]
+use crate::{
+    ethereum_client::EthereumClient,
+    mock_node::{spawn_mock_chain, MockChain, MockNode, MockReply},
+    retry::RetryPolicy,
+};
+
+/// Where a sent tx is at.
+///
+/// A `Dropped` tx left its nonce free, it can be released or reused. A tx `Pending` for too
+/// long is a candidate for a fee bump.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TxStatus {
+    /// Known to the node but not mined yet.
+    Pending,
+    /// Mined with fewer confirmations than required.
+    Included {
+        block_number: u64,
+        confirmations: u64,
+    },
+    /// Mined with the required confirmations, the receipt may still report a revert.
+    Confirmed(Box<TransactionReceipt>),
+    /// Another tx with the same nonce got mined.
+    Replaced,
+    /// Neither mined nor known to the node anymore, the nonce is still free.
+    Dropped,
+    TimedOut,
+}
+
+impl TxStatus {
+    /// No status comes after a final one.
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            TxStatus::Confirmed(_) | TxStatus::Replaced | TxStatus::Dropped | TxStatus::TimedOut
+        )
+    }
+}
+
+/// A sent tx and the nonce it takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrackedTx {
+    pub tx_hash: H256,
+    pub from: Address,
+    pub nonce: u64,
+}
+
+impl TrackedTx {
+    pub fn new(tx_hash: H256, from: Address, nonce: u64) -> Self {
+        Self {
+            tx_hash,
+            from,
+            nonce,
+        }
+    }
+
+    /// Track the signed raw tx, as sent with `send_raw_tx`.
+    pub fn from_raw_tx(tx_bytes: &Bytes) -> Result<Self> {
+        let (tx, _, from) = account::verify::decode_signed_tx(tx_bytes)?;
+        let nonce = tx
+            .nonce()
+            .ok_or_else(|| anyhow!("signed tx without nonce"))?;
+        Ok(Self::new(
+            H256(ethers::utils::keccak256(tx_bytes)),
+            from,
+            nonce.as_u64(),
+        ))
+    }
+}
+
+/// Follow sent txs until they are confirmed, replaced, dropped or time out.
+pub struct ConfirmationTracker<'a, C: EthereumClientTrait> {
+    client: &'a C,
+    confirmations: u64,
+    timeout: Duration,
+    poll_interval: Duration,
+    drop_after_polls: u32,
+}
+
+impl<'a, C: EthereumClientTrait> ConfirmationTracker<'a, C> {
+    pub fn new(client: &'a C) -> Self {
+        Self {
+            client,
+            confirmations: 1,
+            timeout: Duration::from_secs(180),
+            poll_interval: Duration::from_secs(2),
+            drop_after_polls: 5,
+        }
+    }
+
+    /// Blocks on top of the tx, the block of the tx counts as the first one.
+    pub fn with_confirmations(mut self, confirmations: u64) -> Self {
+        self.confirmations = confirmations.max(1);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// A tx the node does not know for this many polls in a row is dropped.
+    pub fn with_drop_after_polls(mut self, drop_after_polls: u32) -> Self {
+        self.drop_after_polls = drop_after_polls.max(1);
+        self
+    }
+
+    /// Poll every `poll_interval`, see [`Self::track_on`].
+    pub fn track(&self, tx: TrackedTx) -> impl Stream<Item = Result<TxStatus>> + '_ {
+        let interval = tokio::time::interval(self.poll_interval);
+        let ticks = futures::stream::unfold(interval, |mut interval| async move {
+            interval.tick().await;
+            Some(((), interval))
+        });
+        self.track_on(tx, ticks)
+    }
+
+    /// Poll on every tick, e.g. on the new heads of a websocket subscription.
+    ///
+    /// Yields every status change, request errors are yielded without ending the stream.
+    /// The stream ends after a final status or when the ticks end.
+    pub fn track_on<'s>(
+        &'s self,
+        tx: TrackedTx,
+        ticks: impl Stream<Item = ()> + 's,
+    ) -> impl Stream<Item = Result<TxStatus>> + 's {
+        let state = TrackState {
+            ticks: Box::pin(ticks),
+            deadline: Instant::now() + self.timeout,
+            last_status: None,
+            missing_polls: 0,
+            done: false,
+        };
+
+        futures::stream::unfold(state, move |mut state| async move {
+            if state.done {
+                return None;
+            }
+
+            loop {
+                tokio::select! {
+                    tick = state.ticks.next() => tick?,
+                    _ = tokio::time::sleep_until(state.deadline) => {
+                        state.done = true;
+                        return Some((Ok(TxStatus::TimedOut), state));
+                    }
+                }
+
+                // The requests of a poll are retried, they must not run past the deadline.
+                let poll = tokio::time::timeout_at(
+                    state.deadline,
+                    self.poll_status(&tx, &mut state.missing_polls),
+                )
+                .await;
+                let status = match poll {
+                    Ok(Ok(status)) => status,
+                    Ok(Err(e)) => return Some((Err(e), state)),
+                    Err(_) => {
+                        state.done = true;
+                        return Some((Ok(TxStatus::TimedOut), state));
+                    }
+                };
+                if state.last_status.as_ref() != Some(&status) {
+                    state.done = status.is_final();
+                    state.last_status = Some(status.clone());
+                    return Some((Ok(status), state));
+                }
+            }
+        })
+    }
+
+    /// Wait for a final status.
+    ///
+    /// A tx timing out after a request error fails with the error, the status was unknown.
+    pub async fn wait(&self, tx: TrackedTx) -> Result<TxStatus> {
+        let statuses = self.track(tx);
+        futures::pin_mut!(statuses);
+
+        let mut last_status = TxStatus::Pending;
+        let mut last_error = None;
+        while let Some(status) = statuses.next().await {
+            match status {
+                Ok(TxStatus::TimedOut) => last_status = TxStatus::TimedOut,
+                Ok(status) => {
+                    last_status = status;
+                    last_error = None;
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        match last_error {
+            Some(e) => Err(e.context("tx status unknown")),
+            None => Ok(last_status),
+        }
+    }
+
+    async fn poll_status(&self, tx: &TrackedTx, missing_polls: &mut u32) -> Result<TxStatus> {
+        if let Some(receipt) = self.client.get_receipt(tx.tx_hash).await? {
+            *missing_polls = 0;
+            return self.included_status(receipt).await;
+        }
+
+        let chain_nonce = self.client.get_nonce(tx.from).await?;
+        if chain_nonce > tx.nonce.into() {
+            // The tx may have been mined since the receipt was asked for.
+            return match self.client.get_receipt(tx.tx_hash).await? {
+                Some(receipt) => self.included_status(receipt).await,
+                None => Ok(TxStatus::Replaced),
+            };
+        }
+
+        if self.client.get_transaction(tx.tx_hash).await?.is_some() {
+            *missing_polls = 0;
+            return Ok(TxStatus::Pending);
+        }
+
+        *missing_polls += 1;
+        if *missing_polls >= self.drop_after_polls {
+            return Ok(TxStatus::Dropped);
+        }
+        Ok(TxStatus::Pending)
+    }
+
+    async fn included_status(&self, receipt: TransactionReceipt) -> Result<TxStatus> {
+        let block_number = receipt
+            .block_number
+            .ok_or_else(|| anyhow!("receipt without block number"))?
+            .as_u64();
+        let latest_block = self
+            .client
+            .get_block(BlockNumber::Latest.into())
+            .await?
+            .and_then(|block| block.number)
+            .ok_or_else(|| anyhow!("latest block not found"))?
+            .as_u64();
+
+        let confirmations = latest_block.saturating_sub(block_number) + 1;
+        if confirmations >= self.confirmations {
+            return Ok(TxStatus::Confirmed(Box::new(receipt)));
+        }
+        Ok(TxStatus::Included {
+            block_number,
+            confirmations,
+        })
+    }
+}
+
+struct TrackState<'s> {
+    ticks: Pin<Box<dyn Stream<Item = ()> + 's>>,
+    deadline: Instant,
+    last_status: Option<TxStatus>,
+    missing_polls: u32,
+    done: bool,
+}
+
+#[tokio::test]
+async fn test_on_track_confirmations() {
+    let tracked = TrackedTx::new(H256::repeat_byte(1), Address::zero(), 0);
+    let (node, chain) = spawn_mock_chain(MockChain {
+        in_mempool: true,
+        ..Default::default()
+    })
+    .await;
+
+    let client = EthereumClient::new(node.url()).unwrap();
+    let tracker = ConfirmationTracker::new(&client)
+        .with_confirmations(2)
+        .with_poll_interval(Duration::from_millis(10));
+    let statuses = tracker.track(tracked);
+    futures::pin_mut!(statuses);
+
+    assert_eq!(statuses.next().await.unwrap().unwrap(), TxStatus::Pending);
+    {
+        let mut chain = chain.lock().unwrap();
+        chain.head = 100;
+        chain.nonce = 1;
+        chain.mined = Some((tracked.tx_hash, 100));
+    }
+    assert_eq!(
+        statuses.next().await.unwrap().unwrap(),
+        TxStatus::Included {
+            block_number: 100,
+            confirmations: 1
+        }
+    );
+    chain.lock().unwrap().head = 101;
+    let TxStatus::Confirmed(receipt) = statuses.next().await.unwrap().unwrap() else {
+        panic!("expected a confirmed tx");
+    };
+    assert_eq!(receipt.transaction_hash, tracked.tx_hash);
+    assert!(statuses.next().await.is_none());
+}
+
+#[tokio::test]
+async fn test_on_track_replaced_dropped_and_timed_out() {
+    let tracked = TrackedTx::new(H256::repeat_byte(1), Address::zero(), 0);
+    let (node, chain) = spawn_mock_chain(MockChain::default()).await;
+    let client = EthereumClient::new(node.url()).unwrap();
+    let tracker = ConfirmationTracker::new(&client)
+        .with_poll_interval(Duration::from_millis(10))
+        .with_drop_after_polls(3);
+
+    // Never seen by the node.
+    assert_eq!(tracker.wait(tracked).await.unwrap(), TxStatus::Dropped);
+
+    // The nonce got used by another tx.
+    chain.lock().unwrap().nonce = 1;
+    assert_eq!(tracker.wait(tracked).await.unwrap(), TxStatus::Replaced);
+
+    {
+        let mut chain = chain.lock().unwrap();
+        chain.nonce = 0;
+        chain.in_mempool = true;
+    }
+    let tracker = tracker.with_timeout(Duration::from_millis(50));
+    assert_eq!(tracker.wait(tracked).await.unwrap(), TxStatus::TimedOut);
+}
+
+#[tokio::test]
+async fn test_on_wait_fails_with_request_errors() {
+    let node = MockNode::spawn(|_, _| MockReply::error(-32000, "header not found"))
+        .await
+        .unwrap();
+    let client = EthereumClient::new(node.url())
+        .unwrap()
+        .with_retry_policy(RetryPolicy::no_retry());
+    let tracker = ConfirmationTracker::new(&client)
+        .with_poll_interval(Duration::from_millis(10))
+        .with_timeout(Duration::from_millis(50));
+
+    let tracked = TrackedTx::new(H256::repeat_byte(1), Address::zero(), 0);
+    let start = Instant::now();
+    let err = tracker.wait(tracked).await.unwrap_err();
+    assert!(format!("{:#}", err).contains("header not found"));
+    assert!(start.elapsed() < Duration::from_millis(500));
+
+    // The retries of a poll stop at the deadline too.
+    let client = EthereumClient::new(node.url()).unwrap();
+    let tracker = ConfirmationTracker::new(&client).with_timeout(Duration::from_millis(50));
+    let start = Instant::now();
+    assert_eq!(tracker.wait(tracked).await.unwrap(), TxStatus::TimedOut);
+    assert!(start.elapsed() < Duration::from_millis(500));
+}
@@ -1,10 +1,12 @@
-use anyhow::Result;
 use ethers::contract::abigen;
-use ethers::middleware::SignerMiddleware;
-use ethers::providers::Http;
-use ethers::providers::Provider;
-use ethers::types::Address;
-use std::sync::Arc;
+#[cfg(test)]
+use {
+    crate::mock_node::MockNode,
+    anyhow::Result,
+    ethers::providers::{Http, Provider},
+    ethers::types::{Address, U256},
+    std::sync::Arc,
+};
 
 // https://eips.ethereum.org/EIPS/eip-20
 abigen!(
@@ -27,7 +29,10 @@ abigen!(
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
 async fn test_on_erc20_call() -> Result<()> {
-    let provider_arc = Arc::new(Provider::<Http>::try_from("https://rpc.ankr.com/eth")?);
+    let node = MockNode::start().await?;
+    node.load_fixtures(include_str!("../fixtures/mainnet_calls.json"))?;
+
+    let provider_arc = Arc::new(Provider::<Http>::try_from(node.url())?);
     let birdring_account = "0x162c6270266667ccf5a9ed752b5d6a2bdc0f90de".parse()?;
     let rss3_token: Address = "0xc98d64da73a6616c42117b582e832812e7b8d57f".parse()?;
     let usdt_token: Address = "0xdAC17F958D2ee523a2206206994597C13D831ec7".parse()?;
@@ -42,17 +47,18 @@ async fn test_on_erc20_call() -> Result<()> {
     let rss3_total_supply = erc20_contract.total_supply().call().await?;
     let rss3_balance = erc20_contract.balance_of(birdring_account).call().await?;
 
-    println!("rss3 decimals: {}", rss3_decimals);
-    println!("rss3 name: {}", rss3_name);
-    println!("rss3 symbol: {}", rss3_symbol);
-    println!("rss3 total supply: {}", rss3_total_supply);
+    assert_eq!(rss3_decimals, 18);
+    assert_eq!(rss3_name, "RSS3");
+    assert_eq!(rss3_symbol, "RSS3");
+    assert_eq!(rss3_total_supply, U256::exp10(27));
+    assert_eq!(rss3_balance, U256::exp10(21));
 
     let usdt_name = usdt_contract.name().call().await?;
     let usdt_symbol = usdt_contract.symbol().call().await?;
 
-    println!("usdt name: {}", usdt_name);
-    println!("usdt symbol: {}", usdt_symbol);
+    assert_eq!(usdt_name, "Tether USD");
+    assert_eq!(usdt_symbol, "USDT");
 
-    println!("birdring rss3 balance: {}", rss3_balance);
+    node.assert_called("eth_call", 7);
     Ok(())
 }
@@ -1,9 +1,12 @@
-use anyhow::{Ok, Result};
 use ethers::contract::abigen;
-use ethers::providers::Http;
-use ethers::providers::Provider;
-use ethers::types::Address;
-use std::sync::Arc;
+#[cfg(test)]
+use {
+    crate::mock_node::MockNode,
+    anyhow::{Ok, Result},
+    ethers::providers::{Http, Provider},
+    ethers::types::Address,
+    std::sync::Arc,
+};
 
 // https://eips.ethereum.org/EIPS/eip-721
 abigen!(
@@ -29,7 +32,10 @@ abigen!(
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
 async fn test_on_erc721_call() -> Result<()> {
-    let provider_arc = Arc::new(Provider::<Http>::try_from("https://rpc.ankr.com/eth")?);
+    let node = MockNode::start().await?;
+    node.load_fixtures(include_str!("../fixtures/mainnet_calls.json"))?;
+
+    let provider_arc = Arc::new(Provider::<Http>::try_from(node.url())?);
     let birdring_account = "0x162c6270266667ccf5a9ed752b5d6a2bdc0f90de".parse()?;
     let rss3_genesis_nft: Address = "0x5452c7fb99d99fab3cc1875e9da9829cb50f7a13".parse()?;
 
@@ -42,9 +48,9 @@ async fn test_on_erc721_call() -> Result<()> {
     let name = rss3_nft_contract.name().call().await?;
     let symbol = rss3_nft_contract.symbol().call().await?;
 
-    println!("rss3 nft balance: {}", balance);
-    println!("rss3 nft name: {}", name);
-    println!("rss3 nft symbol: {}", symbol);
+    assert_eq!(balance, 2.into());
+    assert_eq!(name, "RSS3 Genesis");
+    assert_eq!(symbol, "RSS3G");
 
     Ok(())
 }
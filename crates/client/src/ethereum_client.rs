@@ -1,17 +1,34 @@
-use std::{fmt::format, future::pending, sync::Arc, thread};
-
-use tokio::sync::mpsc;
-use tokio::task::{JoinHandle, JoinSet};
+use std::{
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
 
 use anyhow::{anyhow, Ok, Result};
 use ethers::{
-    providers::{Http, JsonRpcClient, Middleware, Provider, Ws},
-    types::{transaction::eip2718::TypedTransaction, Bytes, NameOrAddress, Res, H160, U256},
+    providers::{Http, JsonRpcClient, Middleware, Provider},
+    types::{
+        transaction::eip2718::TypedTransaction, Block, BlockId, BlockNumber, Bytes, FeeHistory,
+        Filter, Log, Transaction, TransactionReceipt, H256, U256, U64,
+    },
+    utils::keccak256,
+};
+
+use crate::{
+    broadcast::{broadcast, BroadcastPolicy, BroadcastReport},
+    health::{rank, update_block_lag, EndpointHealth, HealthConfig},
+    retry::{retry, RateLimiter, RetryPolicy},
+    transport::Transport,
 };
 
-use account::{Account, AccountNonce};
+use account::{Account, AccountSigner, NonceProvider};
 use ethers::core::types::Address;
-use tracing::info;
+
+#[cfg(test)]
+use {
+    crate::mock_node::{test_account, MockNode, MockReply},
+    ethers::types::TransactionRequest,
+    std::sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
 
 pub struct EthereumClient<M>
 where
@@ -19,21 +36,107 @@ where
 {
     provider: Arc<Provider<M>>,
     endpoint: String,
+    retry_policy: RetryPolicy,
+    /// Shared by the clones, the limit is per endpoint.
+    rate_limiter: Option<Arc<RateLimiter>>,
 }
 
+// Not derived, the transport itself does not need to be `Clone`.
+impl<M: JsonRpcClient> Clone for EthereumClient<M> {
+    fn clone(&self) -> Self {
+        Self {
+            provider: Arc::clone(&self.provider),
+            endpoint: self.endpoint.clone(),
+            retry_policy: self.retry_policy,
+            rate_limiter: self.rate_limiter.clone(),
+        }
+    }
+}
+
+#[allow(async_fn_in_trait)]
 pub trait EthereumClientTrait {
     async fn send_tx(&self, tx: TypedTransaction) -> Result<String>;
     async fn send_raw_tx(&self, tx_bytes: Bytes) -> Result<String>;
     async fn get_nonce(&self, addr: Address) -> Result<U256>;
+
+    /// State reads take the block to read at, `None` is the latest block.
+    async fn get_balance(&self, addr: Address, block: Option<BlockId>) -> Result<U256>;
+    async fn get_transaction_count(&self, addr: Address, block: Option<BlockId>) -> Result<U256>;
+    async fn get_storage_at(
+        &self,
+        addr: Address,
+        slot: H256,
+        block: Option<BlockId>,
+    ) -> Result<H256>;
+    async fn get_block(&self, block: BlockId) -> Result<Option<Block<H256>>>;
+    async fn get_transaction(&self, tx_hash: H256) -> Result<Option<Transaction>>;
+    async fn get_receipt(&self, tx_hash: H256) -> Result<Option<TransactionReceipt>>;
+    /// The block range is part of the filter.
+    async fn get_logs(&self, filter: &Filter) -> Result<Vec<Log>>;
+    async fn call(&self, tx: &TypedTransaction, block: Option<BlockId>) -> Result<Bytes>;
+    async fn estimate_gas(&self, tx: &TypedTransaction, block: Option<BlockId>) -> Result<U256>;
+    async fn chain_id(&self) -> Result<U256>;
+    async fn block_number(&self) -> Result<U64>;
+    async fn gas_price(&self) -> Result<U256>;
+    async fn fee_history(
+        &self,
+        block_count: u64,
+        last_block: BlockNumber,
+        reward_percentiles: &[f64],
+    ) -> Result<FeeHistory>;
+    async fn get_code(&self, addr: Address, block: Option<BlockId>) -> Result<Bytes>;
 }
 
 impl EthereumClient<Http> {
     pub fn new(endpoint: String) -> Result<Self> {
         let provider = Provider::<Http>::try_from(endpoint.clone())?;
-        Ok(Self {
+        Ok(Self::new_with_provider(provider, endpoint))
+    }
+}
+
+impl EthereumClient<Transport> {
+    /// Connect to a http, websocket or IPC endpoint, see [`Transport`].
+    pub async fn connect(endpoint: String) -> Result<Self> {
+        let transport = Transport::connect(&endpoint).await?;
+        Ok(Self::new_with_provider(Provider::new(transport), endpoint))
+    }
+}
+
+impl<M: JsonRpcClient> EthereumClient<M> {
+    pub fn new_with_provider(provider: Provider<M>, endpoint: String) -> Self {
+        Self {
             provider: Arc::new(provider),
             endpoint,
-        })
+            retry_policy: RetryPolicy::default(),
+            rate_limiter: None,
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Send at most `requests_per_second` requests to the endpoint.
+    pub fn with_rate_limit(mut self, requests_per_second: u32) -> Self {
+        self.rate_limiter = Some(Arc::new(RateLimiter::new(requests_per_second)));
+        self
+    }
+
+    async fn retry<T, F, Fut>(&self, retry_policy: &RetryPolicy, request: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<T>>,
+    {
+        retry(retry_policy, self.rate_limiter.as_deref(), request).await
+    }
+
+    pub fn provider(&self) -> Arc<Provider<M>> {
+        Arc::clone(&self.provider)
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
     }
 
     pub fn endpoint_info(&self) -> String {
@@ -41,69 +144,723 @@ impl EthereumClient<Http> {
     }
 }
 
-impl EthereumClientTrait for EthereumClient<Http> {
+impl<M: JsonRpcClient> EthereumClientTrait for EthereumClient<M> {
     async fn send_tx(&self, tx: TypedTransaction) -> Result<String> {
-        let pending_tx = self.provider.send_transaction(tx, None).await?;
-        Ok(format!("{:#x}", pending_tx.tx_hash()))
+        // Without a nonce the node could sign and send a retried tx twice.
+        let retry_policy = match tx.nonce() {
+            Some(_) => self.retry_policy,
+            None => RetryPolicy::no_retry(),
+        };
+        self.retry(&retry_policy, || async {
+            let pending_tx = self.provider.send_transaction(tx.clone(), None).await?;
+            Ok(format!("{:#x}", pending_tx.tx_hash()))
+        })
+        .await
     }
 
     async fn send_raw_tx(&self, tx_bytes: Bytes) -> Result<String> {
-        let pending_tx = self.provider.send_raw_transaction(tx_bytes.clone()).await?;
-        Ok(format!("{:#x}", pending_tx.tx_hash()))
+        let tx_hash = format!("{:#x}", H256(keccak256(&tx_bytes)));
+        let result = self
+            .retry(&self.retry_policy, || async {
+                let pending_tx = self.provider.send_raw_transaction(tx_bytes.clone()).await?;
+                Ok(format!("{:#x}", pending_tx.tx_hash()))
+            })
+            .await;
+
+        match result {
+            // An attempt that timed out may have reached the node before the retry.
+            Err(e) if e.to_string().contains("already known") => Ok(tx_hash),
+            result => result,
+        }
     }
 
     async fn get_nonce(&self, addr: Address) -> Result<U256> {
-        let nonce = self.provider.get_transaction_count(addr, None).await?;
-        Ok(nonce)
+        self.retry(&self.retry_policy, || async {
+            let nonce = self.provider.get_transaction_count(addr, None).await?;
+            Ok(nonce)
+        })
+        .await
+    }
+
+    async fn get_balance(&self, addr: Address, block: Option<BlockId>) -> Result<U256> {
+        self.retry(&self.retry_policy, || async {
+            Ok(self.provider.get_balance(addr, block).await?)
+        })
+        .await
+    }
+
+    async fn get_transaction_count(&self, addr: Address, block: Option<BlockId>) -> Result<U256> {
+        self.retry(&self.retry_policy, || async {
+            Ok(self.provider.get_transaction_count(addr, block).await?)
+        })
+        .await
+    }
+
+    async fn get_storage_at(
+        &self,
+        addr: Address,
+        slot: H256,
+        block: Option<BlockId>,
+    ) -> Result<H256> {
+        self.retry(&self.retry_policy, || async {
+            Ok(self.provider.get_storage_at(addr, slot, block).await?)
+        })
+        .await
+    }
+
+    async fn get_block(&self, block: BlockId) -> Result<Option<Block<H256>>> {
+        self.retry(&self.retry_policy, || async {
+            Ok(self.provider.get_block(block).await?)
+        })
+        .await
+    }
+
+    async fn get_transaction(&self, tx_hash: H256) -> Result<Option<Transaction>> {
+        self.retry(&self.retry_policy, || async {
+            Ok(self.provider.get_transaction(tx_hash).await?)
+        })
+        .await
+    }
+
+    async fn get_receipt(&self, tx_hash: H256) -> Result<Option<TransactionReceipt>> {
+        self.retry(&self.retry_policy, || async {
+            Ok(self.provider.get_transaction_receipt(tx_hash).await?)
+        })
+        .await
+    }
+
+    async fn get_logs(&self, filter: &Filter) -> Result<Vec<Log>> {
+        self.retry(&self.retry_policy, || async {
+            Ok(self.provider.get_logs(filter).await?)
+        })
+        .await
+    }
+
+    async fn call(&self, tx: &TypedTransaction, block: Option<BlockId>) -> Result<Bytes> {
+        self.retry(&self.retry_policy, || async {
+            Ok(self.provider.call(tx, block).await?)
+        })
+        .await
+    }
+
+    async fn estimate_gas(&self, tx: &TypedTransaction, block: Option<BlockId>) -> Result<U256> {
+        self.retry(&self.retry_policy, || async {
+            Ok(self.provider.estimate_gas(tx, block).await?)
+        })
+        .await
+    }
+
+    async fn chain_id(&self) -> Result<U256> {
+        self.retry(&self.retry_policy, || async {
+            Ok(self.provider.get_chainid().await?)
+        })
+        .await
+    }
+
+    async fn block_number(&self) -> Result<U64> {
+        self.retry(&self.retry_policy, || async {
+            Ok(self.provider.get_block_number().await?)
+        })
+        .await
+    }
+
+    async fn gas_price(&self) -> Result<U256> {
+        self.retry(&self.retry_policy, || async {
+            Ok(self.provider.get_gas_price().await?)
+        })
+        .await
+    }
+
+    async fn fee_history(
+        &self,
+        block_count: u64,
+        last_block: BlockNumber,
+        reward_percentiles: &[f64],
+    ) -> Result<FeeHistory> {
+        self.retry(&self.retry_policy, || async {
+            Ok(self
+                .provider
+                .fee_history(block_count, last_block, reward_percentiles)
+                .await?)
+        })
+        .await
+    }
+
+    async fn get_code(&self, addr: Address, block: Option<BlockId>) -> Result<Bytes> {
+        self.retry(&self.retry_policy, || async {
+            Ok(self.provider.get_code(addr, block).await?)
+        })
+        .await
+    }
+}
+
+impl<M: JsonRpcClient> NonceProvider for EthereumClient<M> {
+    async fn pending_nonce(&self, addr: Address) -> Result<u64> {
+        self.retry(&self.retry_policy, || async {
+            let nonce = self
+                .provider
+                .get_transaction_count(addr, Some(BlockNumber::Pending.into()))
+                .await?;
+            Ok(nonce.as_u64())
+        })
+        .await
     }
 }
 
+/// A health-checked pool of http, websocket and IPC clients.
+///
+/// Reads go to the best ranked endpoint, txs are broadcast to every endpoint not evicted.
 pub struct EthereumClients {
-    http_clients: Vec<EthereumClient<Http>>,
+    endpoints: Vec<String>,
+    /// `None` for the endpoints not connected, the probes connect them again.
+    clients: RwLock<Vec<Option<EthereumClient<Transport>>>>,
+    health: RwLock<Vec<EndpointHealth>>,
+    health_config: HealthConfig,
+    broadcast_policy: BroadcastPolicy,
+    /// Applied to the endpoints connected by the probes too.
+    retry_policy: RetryPolicy,
+    rate_limit: Option<u32>,
 }
 
 impl EthereumClients {
+    /// Connect to every endpoint, failing only if none of them connects. The endpoints that
+    /// failed to connect are retried by [`Self::probe`].
     pub async fn new(rpc_endpoints: Vec<String>) -> Result<Self> {
-        let mut http_clients = vec![];
+        let connects = rpc_endpoints.iter().map(|endpoint| async move {
+            (endpoint, EthereumClient::connect(endpoint.clone()).await)
+        });
+        let connects = futures::future::join_all(connects).await;
 
-        for endpoint in rpc_endpoints {
-            if endpoint.starts_with("http") {
-                let client = EthereumClient::new(endpoint)?;
-                http_clients.push(client);
+        let health_config = HealthConfig::default();
+        let mut clients = vec![];
+        let mut health = vec![];
+        let mut errors = vec![];
+        for (endpoint, connect) in connects {
+            let mut endpoint_health = EndpointHealth::new(endpoint.clone());
+            match connect {
+                std::result::Result::Ok(client) => clients.push(Some(client)),
+                Err(e) => {
+                    endpoint_health
+                        .record_failure(format!("connect failed: {}", e), &health_config);
+                    errors.push(format!("{}: {}", endpoint, e));
+                    clients.push(None);
+                }
             }
+            health.push(endpoint_health);
         }
 
-        if http_clients.is_empty() {
-            return Err(anyhow!("no endpoints available"));
+        if clients.iter().all(Option::is_none) {
+            return Err(anyhow!("no endpoints available: {}", errors.join(", ")));
         }
 
-        Ok(Self { http_clients })
+        Ok(Self {
+            endpoints: rpc_endpoints,
+            clients: RwLock::new(clients),
+            health: RwLock::new(health),
+            health_config,
+            broadcast_policy: BroadcastPolicy::default(),
+            retry_policy: RetryPolicy::default(),
+            rate_limit: None,
+        })
     }
 
-    pub async fn sign_and_send_tx(self, tx: &TypedTransaction, account: Account) -> Result<String> {
-        let tx_bytes = account.sign_tx(tx).await?;
-        let mut task_set = JoinSet::new();
+    pub fn with_health_config(mut self, health_config: HealthConfig) -> Self {
+        self.health_config = health_config;
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        for client in self.clients.get_mut().unwrap().iter_mut().flatten() {
+            *client = client.clone().with_retry_policy(retry_policy);
+        }
+        self
+    }
 
-        for client in self.http_clients {
-            let temp_bytes = tx_bytes.clone();
-            task_set.spawn(async move { client.send_raw_tx(temp_bytes).await });
+    /// Send at most `requests_per_second` requests to each endpoint.
+    pub fn with_rate_limit(mut self, requests_per_second: u32) -> Self {
+        self.rate_limit = Some(requests_per_second);
+        for client in self.clients.get_mut().unwrap().iter_mut().flatten() {
+            *client = client.clone().with_rate_limit(requests_per_second);
         }
+        self
+    }
 
-        while let Some(_) = task_set.join_next().await {}
+    pub fn with_broadcast_policy(mut self, broadcast_policy: BroadcastPolicy) -> Self {
+        self.broadcast_policy = broadcast_policy;
+        self
+    }
 
-        Ok("".to_string())
+    pub fn health(&self) -> Vec<EndpointHealth> {
+        self.health.read().unwrap().clone()
     }
 
-    pub async fn send_tx(self, tx: &TypedTransaction) -> Result<String> {
-        let mut task_set = JoinSet::new();
+    /// Probe every endpoint with `eth_blockNumber` and `eth_chainId`, connecting the endpoints
+    /// not connected yet.
+    ///
+    /// The probes count against the rate limit of the endpoint, waiting for it is not latency.
+    pub async fn probe(&self) {
+        let probes = (0..self.endpoints.len()).map(|i| self.probe_endpoint(i));
+        let probes = futures::future::join_all(probes).await;
 
-        for client in self.http_clients {
-            let temp_tx = tx.clone();
-            task_set.spawn(async move { client.send_tx(temp_tx).await });
+        let mut healths = self.health.write().unwrap();
+        for (health, (probe, latency)) in healths.iter_mut().zip(probes) {
+            match probe {
+                std::result::Result::Ok((block_number, chain_id)) => {
+                    health.record_success(latency, block_number, chain_id, &self.health_config)
+                }
+                Err(e) => health.record_failure(e, &self.health_config),
+            }
         }
+        update_block_lag(&mut healths);
+    }
+
+    /// Return the block number and chain id of the endpoint, and the latency of the probe.
+    async fn probe_endpoint(
+        &self,
+        i: usize,
+    ) -> (std::result::Result<(u64, u64), String>, Duration) {
+        let client = self.clients.read().unwrap()[i].clone();
+        let client = match client {
+            Some(client) => client,
+            None => match self.reconnect(i).await {
+                std::result::Result::Ok(client) => client,
+                Err(e) => return (Err(format!("connect failed: {}", e)), Duration::ZERO),
+            },
+        };
 
-        while let Some(_) = task_set.join_next().await {}
+        if let Some(rate_limiter) = &client.rate_limiter {
+            rate_limiter.acquire().await;
+            rate_limiter.acquire().await;
+        }
+        let provider = client.provider();
+        let start = Instant::now();
+        let probe = tokio::time::timeout(self.health_config.probe_timeout, async {
+            futures::try_join!(provider.get_block_number(), provider.get_chainid())
+        })
+        .await;
+        let latency = start.elapsed();
 
-        Ok("".to_string())
+        let probe = match probe {
+            std::result::Result::Ok(std::result::Result::Ok((block_number, chain_id))) => {
+                std::result::Result::Ok((block_number.as_u64(), chain_id.as_u64()))
+            }
+            std::result::Result::Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => Err("probe timed out".to_string()),
+        };
+        (probe, latency)
     }
+
+    async fn reconnect(&self, i: usize) -> Result<EthereumClient<Transport>> {
+        let connect = EthereumClient::connect(self.endpoints[i].clone());
+        let mut client = tokio::time::timeout(self.health_config.probe_timeout, connect)
+            .await
+            .map_err(|_| anyhow!("timed out"))??
+            .with_retry_policy(self.retry_policy);
+        if let Some(requests_per_second) = self.rate_limit {
+            client = client.with_rate_limit(requests_per_second);
+        }
+
+        self.clients.write().unwrap()[i] = Some(client.clone());
+        Ok(client)
+    }
+
+    /// Probe the pool every `probe_interval` until the pool is dropped.
+    pub fn spawn_health_checks(self: &Arc<Self>) -> tokio::task::JoinHandle<()> {
+        let pool = Arc::downgrade(self);
+        let mut interval = tokio::time::interval(self.health_config.probe_interval);
+        tokio::spawn(async move {
+            loop {
+                interval.tick().await;
+                let Some(pool) = pool.upgrade() else {
+                    return;
+                };
+                pool.probe().await;
+            }
+        })
+    }
+
+    /// The endpoint reads should go to.
+    pub fn best_client(&self) -> Result<EthereumClient<Transport>> {
+        let healths = self.health.read().unwrap();
+        let clients = self.clients.read().unwrap();
+        rank(&healths, &self.health_config)
+            .into_iter()
+            .find_map(|i| clients[i].clone())
+            .ok_or_else(|| anyhow!("no healthy endpoint available"))
+    }
+
+    /// The endpoints writes fan out to, every connected endpoint if all of them are evicted.
+    fn write_clients(&self) -> Vec<EthereumClient<Transport>> {
+        let healths = self.health.read().unwrap();
+        let all_clients = self.clients.read().unwrap();
+        let clients = all_clients
+            .iter()
+            .zip(healths.iter())
+            .filter(|(_, health)| !health.is_evicted(&self.health_config))
+            .filter_map(|(client, _)| client.clone())
+            .collect::<Vec<_>>();
+
+        if clients.is_empty() {
+            return all_clients.iter().flatten().cloned().collect();
+        }
+        clients
+    }
+
+    /// Sign the tx once and broadcast the raw tx to every endpoint.
+    pub async fn sign_and_send_tx<S: AccountSigner>(
+        &self,
+        tx: &TypedTransaction,
+        account: &Account<S>,
+    ) -> Result<BroadcastReport> {
+        let tx_bytes = account.sign_tx(tx).await?;
+        Ok(self.broadcast_raw_tx(tx_bytes).await)
+    }
+
+    /// Broadcast a signed raw tx to every endpoint.
+    pub async fn broadcast_raw_tx(&self, tx_bytes: Bytes) -> BroadcastReport {
+        let tx_hash = format!("{:#x}", H256(keccak256(&tx_bytes)));
+
+        let sends = self.write_clients().into_iter().map(|client| {
+            let tx_bytes = tx_bytes.clone();
+            (client.endpoint.clone(), async move {
+                client.send_raw_tx(tx_bytes).await
+            })
+        });
+
+        broadcast(sends, self.broadcast_policy, Some(tx_hash)).await
+    }
+
+    /// Send the tx to every endpoint, each node signs it with its own unlocked account.
+    pub async fn send_tx(&self, tx: &TypedTransaction) -> Result<BroadcastReport> {
+        let sends = self.write_clients().into_iter().map(|client| {
+            let tx = tx.clone();
+            (
+                client.endpoint.clone(),
+                async move { client.send_tx(tx).await },
+            )
+        });
+
+        Ok(broadcast(sends, self.broadcast_policy, None).await)
+    }
+}
+
+/// Reads go to the best endpoint, txs are broadcast with the pool policy.
+impl EthereumClientTrait for EthereumClients {
+    async fn send_tx(&self, tx: TypedTransaction) -> Result<String> {
+        broadcast_tx_hash(EthereumClients::send_tx(self, &tx).await?)
+    }
+
+    async fn send_raw_tx(&self, tx_bytes: Bytes) -> Result<String> {
+        broadcast_tx_hash(self.broadcast_raw_tx(tx_bytes).await)
+    }
+
+    async fn get_nonce(&self, addr: Address) -> Result<U256> {
+        self.best_client()?.get_nonce(addr).await
+    }
+
+    async fn get_balance(&self, addr: Address, block: Option<BlockId>) -> Result<U256> {
+        self.best_client()?.get_balance(addr, block).await
+    }
+
+    async fn get_transaction_count(&self, addr: Address, block: Option<BlockId>) -> Result<U256> {
+        self.best_client()?.get_transaction_count(addr, block).await
+    }
+
+    async fn get_storage_at(
+        &self,
+        addr: Address,
+        slot: H256,
+        block: Option<BlockId>,
+    ) -> Result<H256> {
+        self.best_client()?.get_storage_at(addr, slot, block).await
+    }
+
+    async fn get_block(&self, block: BlockId) -> Result<Option<Block<H256>>> {
+        self.best_client()?.get_block(block).await
+    }
+
+    async fn get_transaction(&self, tx_hash: H256) -> Result<Option<Transaction>> {
+        self.best_client()?.get_transaction(tx_hash).await
+    }
+
+    async fn get_receipt(&self, tx_hash: H256) -> Result<Option<TransactionReceipt>> {
+        self.best_client()?.get_receipt(tx_hash).await
+    }
+
+    async fn get_logs(&self, filter: &Filter) -> Result<Vec<Log>> {
+        self.best_client()?.get_logs(filter).await
+    }
+
+    async fn call(&self, tx: &TypedTransaction, block: Option<BlockId>) -> Result<Bytes> {
+        self.best_client()?.call(tx, block).await
+    }
+
+    async fn estimate_gas(&self, tx: &TypedTransaction, block: Option<BlockId>) -> Result<U256> {
+        self.best_client()?.estimate_gas(tx, block).await
+    }
+
+    async fn chain_id(&self) -> Result<U256> {
+        self.best_client()?.chain_id().await
+    }
+
+    async fn block_number(&self) -> Result<U64> {
+        self.best_client()?.block_number().await
+    }
+
+    async fn gas_price(&self) -> Result<U256> {
+        self.best_client()?.gas_price().await
+    }
+
+    async fn fee_history(
+        &self,
+        block_count: u64,
+        last_block: BlockNumber,
+        reward_percentiles: &[f64],
+    ) -> Result<FeeHistory> {
+        self.best_client()?
+            .fee_history(block_count, last_block, reward_percentiles)
+            .await
+    }
+
+    async fn get_code(&self, addr: Address, block: Option<BlockId>) -> Result<Bytes> {
+        self.best_client()?.get_code(addr, block).await
+    }
+}
+
+fn broadcast_tx_hash(report: BroadcastReport) -> Result<String> {
+    if !report.is_success() {
+        let errors = report
+            .results
+            .iter()
+            .filter_map(|r| {
+                r.result
+                    .as_ref()
+                    .err()
+                    .map(|e| format!("{}: {}", r.endpoint, e))
+            })
+            .collect::<Vec<_>>();
+        return Err(anyhow!("tx not accepted: {}", errors.join(", ")));
+    }
+    report
+        .tx_hash
+        .ok_or_else(|| anyhow!("no tx hash in broadcast report"))
+}
+
+#[cfg(test)]
+async fn spawn_mock_node(block_number: Arc<AtomicU64>, failing: bool) -> MockNode {
+    MockNode::spawn(move |method, params| match (method, failing) {
+        (_, true) => MockReply::error(-32005, "limit exceeded"),
+        ("eth_blockNumber", _) => {
+            MockReply::result(U256::from(block_number.load(Ordering::SeqCst)))
+        }
+        ("eth_chainId", _) => MockReply::result(U256::one()),
+        ("eth_sendRawTransaction", _) => {
+            let tx_bytes: Bytes = serde_json::from_value(params[0].clone()).unwrap();
+            MockReply::result(H256(keccak256(&tx_bytes)))
+        }
+        _ => MockReply::error(-32601, "method not found"),
+    })
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_on_health_checked_pool() {
+    let head = Arc::new(AtomicU64::new(100));
+    let lagging_head = Arc::new(AtomicU64::new(90));
+
+    let fast = spawn_mock_node(Arc::clone(&head), false).await;
+    let slow = spawn_mock_node(Arc::clone(&head), false).await;
+    slow.set_delay(Duration::from_millis(100));
+    let lagging = spawn_mock_node(Arc::clone(&lagging_head), false).await;
+    let failing = spawn_mock_node(Arc::clone(&head), true).await;
+
+    let clients = EthereumClients::new(vec![slow.url(), lagging.url(), failing.url(), fast.url()])
+        .await
+        .unwrap()
+        .with_health_config(HealthConfig {
+            chain_id: Some(1),
+            ..HealthConfig::default()
+        });
+
+    clients.probe().await;
+    let health = clients.health();
+    assert_eq!(health[1].block_lag, 10);
+    assert!(health[2].last_error.is_some());
+    assert_eq!(clients.best_client().unwrap().endpoint(), fast.url());
+
+    // The failing node is evicted from writes, the lagging one still gets the tx.
+    let account = test_account();
+    let tx: TypedTransaction = TransactionRequest::new()
+        .to(Address::zero())
+        .nonce(0)
+        .gas(21_000)
+        .gas_price(1)
+        .chain_id(1)
+        .into();
+    let report = clients.sign_and_send_tx(&tx, &account).await.unwrap();
+    assert_eq!(report.results.len(), 3);
+    assert_eq!(report.accepted_count(), 3);
+    assert!(report.results.iter().all(|r| r.endpoint != failing.url()));
+
+    // The lagging node catches up with the head.
+    lagging_head.store(101, Ordering::SeqCst);
+    head.store(101, Ordering::SeqCst);
+    clients.probe().await;
+    assert_eq!(clients.health()[1].block_lag, 0);
+}
+
+#[tokio::test]
+async fn test_on_retry_rate_limited_requests() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let node_calls = Arc::clone(&calls);
+    let node = MockNode::spawn(move |method, _| {
+        let call = node_calls.fetch_add(1, Ordering::SeqCst);
+        match (method, call) {
+            (_, 0) => MockReply::Status(429),
+            (_, 1) => MockReply::error(-32005, "limit exceeded"),
+            ("eth_getTransactionCount", _) => MockReply::result(U256::from(7)),
+            _ => MockReply::error(-32000, "nonce too low"),
+        }
+    })
+    .await
+    .unwrap();
+
+    let client = EthereumClient::new(node.url())
+        .unwrap()
+        .with_retry_policy(RetryPolicy {
+            initial_backoff: Duration::from_millis(10),
+            ..RetryPolicy::default()
+        });
+    assert_eq!(client.pending_nonce(Address::zero()).await.unwrap(), 7);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+    // Fatal errors are not retried.
+    assert!(client.send_raw_tx(Bytes::from(vec![1])).await.is_err());
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+    let client = client.with_rate_limit(10);
+    let start = Instant::now();
+    for _ in 0..3 {
+        client.get_nonce(Address::zero()).await.unwrap();
+    }
+    assert!(start.elapsed() >= Duration::from_millis(200));
+}
+
+#[tokio::test]
+async fn test_on_pool_reads_with_block_tag() {
+    let node = MockNode::spawn(|method, params| match method {
+        "eth_blockNumber" => MockReply::result(U256::from(100)),
+        "eth_chainId" => MockReply::result(U256::one()),
+        "eth_getBalance" if params[1] == "0x5a" => MockReply::result(U256::from(1)),
+        "eth_getBalance" if params[1] == "latest" => MockReply::result(U256::from(2)),
+        "eth_getCode" => MockReply::result(Bytes::from(vec![0x60, 0x80])),
+        "eth_call" if params[1] == "pending" => MockReply::result(Bytes::from(vec![1])),
+        "eth_getTransactionReceipt" => MockReply::Result(serde_json::Value::Null),
+        _ => MockReply::error(-32601, "method not found"),
+    })
+    .await
+    .unwrap();
+
+    let clients = EthereumClients::new(vec![node.url()]).await.unwrap();
+    clients.probe().await;
+
+    assert_eq!(
+        clients
+            .get_balance(Address::zero(), Some(BlockNumber::Number(90.into()).into()))
+            .await
+            .unwrap(),
+        U256::from(1)
+    );
+    assert_eq!(
+        clients.get_balance(Address::zero(), None).await.unwrap(),
+        U256::from(2)
+    );
+    assert_eq!(
+        clients.get_code(Address::zero(), None).await.unwrap(),
+        Bytes::from(vec![0x60, 0x80])
+    );
+    let tx: TypedTransaction = TransactionRequest::new().to(Address::zero()).into();
+    assert_eq!(
+        clients
+            .call(&tx, Some(BlockNumber::Pending.into()))
+            .await
+            .unwrap(),
+        Bytes::from(vec![1])
+    );
+    assert_eq!(clients.chain_id().await.unwrap(), U256::one());
+    assert!(clients.get_receipt(H256::zero()).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_on_pool_with_unreachable_endpoint() {
+    let node = spawn_mock_node(Arc::new(AtomicU64::new(100)), false).await;
+    let unreachable = "/tmp/no-such-node.ipc".to_string();
+
+    let clients = EthereumClients::new(vec![unreachable.clone(), node.url()])
+        .await
+        .unwrap();
+    let health = clients.health();
+    assert!(health[0].last_error.is_some());
+    assert!(health[0].is_evicted(&HealthConfig::default()));
+
+    clients.probe().await;
+    assert!(clients.health()[0].is_evicted(&HealthConfig::default()));
+    assert_eq!(clients.best_client().unwrap().endpoint(), node.url());
+    assert_eq!(
+        clients
+            .broadcast_raw_tx(Bytes::from(vec![1]))
+            .await
+            .results
+            .len(),
+        1
+    );
+
+    assert!(EthereumClients::new(vec![unreachable]).await.is_err());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_on_probe_reconnects_endpoint() {
+    let node = spawn_mock_node(Arc::new(AtomicU64::new(100)), false).await;
+    let path = std::env::temp_dir().join(format!("ethereum-kits-pool-{}.ipc", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let clients = EthereumClients::new(vec![path.to_str().unwrap().to_string(), node.url()])
+        .await
+        .unwrap();
+    clients.probe().await;
+    let health = &clients.health()[0];
+    assert!(health
+        .last_error
+        .as_ref()
+        .unwrap()
+        .contains("connect failed"));
+    assert_eq!(health.block_number, None);
+
+    // The node comes up, the next probe connects it.
+    crate::transport::spawn_ipc_node(path.clone(), false);
+    clients.probe().await;
+    let health = &clients.health()[0];
+    assert_eq!(health.block_number, Some(1));
+    assert_eq!(health.chain_id, Some(1));
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_on_probe_under_rate_limit() {
+    let node = spawn_mock_node(Arc::new(AtomicU64::new(100)), false).await;
+    let clients = EthereumClients::new(vec![node.url()])
+        .await
+        .unwrap()
+        .with_rate_limit(10);
+
+    let start = Instant::now();
+    clients.probe().await;
+    clients.probe().await;
+    // 4 requests spaced by 100ms.
+    assert!(start.elapsed() >= Duration::from_millis(300));
+    assert!(clients.health()[0].latency.unwrap() < Duration::from_millis(100));
 }
@@ -0,0 +1,273 @@
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+use ethers::{
+    providers::JsonRpcClient,
+    types::{BlockNumber, FeeHistory, U256},
+};
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+use crate::ethereum_client::{EthereumClient, EthereumClientTrait};
+
+#[cfg(test)]
+use ethers::providers::{MockProvider, Provider};
+
+/// Default number of blocks of `eth_feeHistory` to look at.
+pub const DEFAULT_BLOCK_WINDOW: u64 = 10;
+
+/// `maxFeePerGas` covers this many times the base fee, enough for 6 full blocks more.
+pub(crate) const BASE_FEE_MULTIPLIER: u64 = 2;
+
+/// EIP-1559 base fee changes at most by 1/8 per block.
+const BASE_FEE_MAX_CHANGE_DENOMINATOR: u64 = 8;
+
+/// How eager the transaction is to get included.
+#[derive(EnumIter, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FeeStrategy {
+    Slow,
+    Normal,
+    Fast,
+    Aggressive,
+}
+
+impl FeeStrategy {
+    /// The reward percentile of `eth_feeHistory` the priority fee is taken from.
+    pub fn percentile(&self) -> f64 {
+        match self {
+            FeeStrategy::Slow => 10.0,
+            FeeStrategy::Normal => 50.0,
+            FeeStrategy::Fast => 75.0,
+            FeeStrategy::Aggressive => 95.0,
+        }
+    }
+
+    fn all_percentiles() -> Vec<f64> {
+        FeeStrategy::iter().map(|s| s.percentile()).collect()
+    }
+
+    fn reward_index(&self) -> usize {
+        *self as usize
+    }
+}
+
+/// The fees to put in an EIP-1559 transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeEstimate {
+    /// The latest block of the fee history.
+    pub block_number: u64,
+    /// The highest base fee the target block can have.
+    pub base_fee: U256,
+    pub max_priority_fee_per_gas: U256,
+    /// Twice the base fee plus the priority fee, so the tx stays valid if it is not
+    /// included in the target block.
+    pub max_fee_per_gas: U256,
+}
+
+impl FeeEstimate {
+    /// Estimate from a fee history fetched with the percentiles of all the strategies.
+    ///
+    /// `blocks_ahead` is the distance from the latest block to the target block,
+    /// 1 targets the next block.
+    pub fn from_fee_history(
+        fee_history: &FeeHistory,
+        strategy: FeeStrategy,
+        blocks_ahead: u64,
+    ) -> Result<Self> {
+        // The last base fee of the history is the one of the next block.
+        let next_base_fee = *fee_history
+            .base_fee_per_gas
+            .last()
+            .ok_or_else(|| anyhow!("empty base fee in fee history"))?;
+        let block_number = history_latest_block(fee_history);
+
+        let base_fee = project_base_fee(next_base_fee, blocks_ahead.saturating_sub(1));
+        let max_priority_fee_per_gas = median_reward(&fee_history.reward, strategy.reward_index());
+
+        Ok(Self {
+            block_number,
+            base_fee,
+            max_priority_fee_per_gas,
+            max_fee_per_gas: base_fee * BASE_FEE_MULTIPLIER + max_priority_fee_per_gas,
+        })
+    }
+}
+
+/// Highest base fee after `blocks` full blocks on top of `base_fee`.
+pub fn project_base_fee(base_fee: U256, blocks: u64) -> U256 {
+    (0..blocks).fold(base_fee, |base_fee, _| {
+        base_fee + (base_fee / BASE_FEE_MAX_CHANGE_DENOMINATOR).max(U256::one())
+    })
+}
+
+/// Median over the window of the non-zero rewards at `index`, empty blocks pay no tip.
+pub(crate) fn median_reward(reward: &[Vec<U256>], index: usize) -> U256 {
+    let mut rewards = reward
+        .iter()
+        .filter_map(|block_reward| block_reward.get(index).copied())
+        .filter(|reward| !reward.is_zero())
+        .collect::<Vec<_>>();
+
+    if rewards.is_empty() {
+        return U256::zero();
+    }
+
+    rewards.sort();
+    rewards[rewards.len() / 2]
+}
+
+/// EIP-1559 fee estimator based on `eth_feeHistory`.
+///
+/// The fee history is fetched once per block with the percentiles of all the strategies,
+/// so estimating with several strategies in the same block costs a single `eth_blockNumber`.
+pub struct FeeOracle<M: JsonRpcClient> {
+    client: EthereumClient<M>,
+    block_window: u64,
+    blocks_ahead: u64,
+    cache: Mutex<Option<FeeHistory>>,
+}
+
+impl<M: JsonRpcClient> FeeOracle<M> {
+    pub fn new(client: EthereumClient<M>) -> Self {
+        Self {
+            client,
+            block_window: DEFAULT_BLOCK_WINDOW,
+            blocks_ahead: 1,
+            cache: Mutex::new(None),
+        }
+    }
+
+    /// Number of blocks of fee history to look at.
+    pub fn with_block_window(mut self, block_window: u64) -> Self {
+        self.block_window = block_window.max(1);
+        self
+    }
+
+    /// Target the block `blocks_ahead` blocks after the latest one, 1 is the next block.
+    pub fn with_blocks_ahead(mut self, blocks_ahead: u64) -> Self {
+        self.blocks_ahead = blocks_ahead.max(1);
+        self
+    }
+
+    pub async fn estimate(&self, strategy: FeeStrategy) -> Result<FeeEstimate> {
+        self.estimate_blocks_ahead(strategy, self.blocks_ahead)
+            .await
+    }
+
+    pub async fn estimate_blocks_ahead(
+        &self,
+        strategy: FeeStrategy,
+        blocks_ahead: u64,
+    ) -> Result<FeeEstimate> {
+        let fee_history = self.fee_history().await?;
+        FeeEstimate::from_fee_history(&fee_history, strategy, blocks_ahead)
+    }
+
+    /// The fee history up to the latest block, fetched again only when a new block is seen.
+    pub async fn fee_history(&self) -> Result<FeeHistory> {
+        let latest_block = self.client.block_number().await?.as_u64();
+
+        if let Some(fee_history) = self.cache.lock().unwrap().as_ref() {
+            if history_latest_block(fee_history) == latest_block {
+                return Ok(fee_history.clone());
+            }
+        }
+
+        let fee_history = self
+            .client
+            .fee_history(
+                self.block_window,
+                BlockNumber::Number(latest_block.into()),
+                &FeeStrategy::all_percentiles(),
+            )
+            .await?;
+        *self.cache.lock().unwrap() = Some(fee_history.clone());
+
+        Ok(fee_history)
+    }
+}
+
+fn history_latest_block(fee_history: &FeeHistory) -> u64 {
+    fee_history.oldest_block.as_u64() + (fee_history.gas_used_ratio.len() as u64).saturating_sub(1)
+}
+
+#[cfg(test)]
+fn fixture_fee_history() -> FeeHistory {
+    serde_json::from_str(include_str!("../fixtures/fee_history_mainnet.json")).unwrap()
+}
+
+#[test]
+fn test_project_base_fee() {
+    let base_fee = U256::from(100_000_000_000u64);
+    assert_eq!(project_base_fee(base_fee, 0), base_fee);
+    assert_eq!(
+        project_base_fee(base_fee, 1),
+        U256::from(112_500_000_000u64)
+    );
+    assert_eq!(
+        project_base_fee(base_fee, 2),
+        U256::from(126_562_500_000u64)
+    );
+    // A tiny base fee still grows.
+    assert_eq!(project_base_fee(U256::from(7), 2), U256::from(9));
+}
+
+#[test]
+fn test_on_estimate_from_fixture() {
+    let fee_history = fixture_fee_history();
+
+    let normal = FeeEstimate::from_fee_history(&fee_history, FeeStrategy::Normal, 1).unwrap();
+    assert_eq!(normal.block_number, 0x12a05f0 + 9);
+    assert_eq!(normal.base_fee, U256::from(0x2e90edd00u64));
+    assert_eq!(
+        normal.max_priority_fee_per_gas,
+        U256::from(1_000_000_000u64)
+    );
+    assert_eq!(
+        normal.max_fee_per_gas,
+        normal.base_fee * 2 + 1_000_000_000u64
+    );
+
+    // Zero rewards of empty blocks are ignored.
+    let slow = FeeEstimate::from_fee_history(&fee_history, FeeStrategy::Slow, 1).unwrap();
+    assert_eq!(slow.max_priority_fee_per_gas, U256::from(100_000_000u64));
+
+    let mut last_priority_fee = U256::zero();
+    for strategy in FeeStrategy::iter() {
+        let estimate = FeeEstimate::from_fee_history(&fee_history, strategy, 1).unwrap();
+        assert!(estimate.max_priority_fee_per_gas >= last_priority_fee);
+        last_priority_fee = estimate.max_priority_fee_per_gas;
+    }
+
+    let three_blocks = FeeEstimate::from_fee_history(&fee_history, FeeStrategy::Normal, 3).unwrap();
+    assert_eq!(three_blocks.base_fee, project_base_fee(normal.base_fee, 2));
+}
+
+#[tokio::test]
+async fn test_on_fee_oracle_caches_per_block() {
+    let (provider, mock) = Provider::<MockProvider>::mocked();
+    let client = EthereumClient::new_with_provider(provider, "mock".to_string());
+    let oracle = FeeOracle::new(client).with_blocks_ahead(2);
+
+    let fee_history = fixture_fee_history();
+    let latest_block = history_latest_block(&fee_history);
+
+    // MockProvider pops responses from the back: a new block comes after two estimates.
+    mock.push(fee_history.clone()).unwrap();
+    mock.push(U256::from(latest_block + 1)).unwrap();
+    mock.push(U256::from(latest_block)).unwrap();
+    mock.push(fee_history).unwrap();
+    mock.push(U256::from(latest_block)).unwrap();
+
+    let fast = oracle.estimate(FeeStrategy::Fast).await.unwrap();
+    let aggressive = oracle.estimate(FeeStrategy::Aggressive).await.unwrap();
+    assert_eq!(
+        fast.base_fee,
+        project_base_fee(U256::from(0x2e90edd00u64), 1)
+    );
+    assert!(aggressive.max_fee_per_gas > fast.max_fee_per_gas);
+
+    // The new block fetches the history again, the last response is consumed.
+    oracle.estimate(FeeStrategy::Fast).await.unwrap();
+    assert!(oracle.estimate(FeeStrategy::Fast).await.is_err());
+}
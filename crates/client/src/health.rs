@@ -0,0 +1,209 @@
+use std::{collections::VecDeque, time::Duration};
+
+/// How endpoints of a pool are probed and when they are considered unhealthy.
+#[derive(Debug, Clone)]
+pub struct HealthConfig {
+    pub probe_interval: Duration,
+    /// A probe slower than this counts as a failure.
+    pub probe_timeout: Duration,
+    /// Nodes further behind the highest block are deprioritized for reads.
+    pub max_block_lag: u64,
+    /// Nodes failing more often than this over the window are evicted.
+    pub max_error_rate: f64,
+    /// Number of latest probes the error rate is computed over.
+    pub error_window: usize,
+    /// Nodes on another chain are evicted.
+    pub chain_id: Option<u64>,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            probe_interval: Duration::from_secs(12),
+            probe_timeout: Duration::from_secs(5),
+            max_block_lag: 3,
+            max_error_rate: 0.5,
+            error_window: 20,
+            chain_id: None,
+        }
+    }
+}
+
+/// What the probes know about an endpoint.
+#[derive(Debug, Clone)]
+pub struct EndpointHealth {
+    pub endpoint: String,
+    /// Latency of the latest successful probe.
+    pub latency: Option<Duration>,
+    pub block_number: Option<u64>,
+    pub chain_id: Option<u64>,
+    /// Blocks behind the highest block seen across the pool.
+    pub block_lag: u64,
+    pub last_error: Option<String>,
+    /// Latest probe outcomes, `true` for a success.
+    outcomes: VecDeque<bool>,
+}
+
+impl EndpointHealth {
+    pub fn new(endpoint: String) -> Self {
+        Self {
+            endpoint,
+            latency: None,
+            block_number: None,
+            chain_id: None,
+            block_lag: 0,
+            last_error: None,
+            outcomes: VecDeque::new(),
+        }
+    }
+
+    pub fn error_rate(&self) -> f64 {
+        if self.outcomes.is_empty() {
+            return 0.0;
+        }
+        let failures = self.outcomes.iter().filter(|ok| !**ok).count();
+        failures as f64 / self.outcomes.len() as f64
+    }
+
+    /// Evicted nodes get neither reads nor writes until their probes recover.
+    pub fn is_evicted(&self, config: &HealthConfig) -> bool {
+        let wrong_chain = matches!(
+            (config.chain_id, self.chain_id),
+            (Some(expected), Some(actual)) if expected != actual
+        );
+        wrong_chain || self.error_rate() > config.max_error_rate
+    }
+
+    /// A node never probed yet is assumed healthy.
+    pub fn is_healthy(&self, config: &HealthConfig) -> bool {
+        !self.is_evicted(config)
+            && self.outcomes.back() != Some(&false)
+            && self.block_lag <= config.max_block_lag
+    }
+
+    pub(crate) fn record_success(
+        &mut self,
+        latency: Duration,
+        block_number: u64,
+        chain_id: u64,
+        config: &HealthConfig,
+    ) {
+        self.latency = Some(latency);
+        self.block_number = Some(block_number);
+        self.chain_id = Some(chain_id);
+        self.last_error = None;
+        self.push_outcome(true, config);
+    }
+
+    pub(crate) fn record_failure(&mut self, error: String, config: &HealthConfig) {
+        self.last_error = Some(error);
+        self.push_outcome(false, config);
+    }
+
+    fn push_outcome(&mut self, ok: bool, config: &HealthConfig) {
+        self.outcomes.push_back(ok);
+        while self.outcomes.len() > config.error_window.max(1) {
+            self.outcomes.pop_front();
+        }
+    }
+}
+
+/// Recompute the lag of every endpoint against the highest block of the pool.
+pub(crate) fn update_block_lag(healths: &mut [EndpointHealth]) {
+    let highest_block = healths
+        .iter()
+        .filter_map(|h| h.block_number)
+        .max()
+        .unwrap_or_default();
+    for health in healths.iter_mut() {
+        health.block_lag = health
+            .block_number
+            .map_or(0, |block_number| highest_block.saturating_sub(block_number));
+    }
+}
+
+/// Indexes of the endpoints from best to worst for reads, evicted endpoints are left out.
+///
+/// Healthy endpoints come first, then the ones lagging or failing their latest probe.
+/// Within each group, the least lagging and then the fastest endpoint wins.
+pub(crate) fn rank(healths: &[EndpointHealth], config: &HealthConfig) -> Vec<usize> {
+    let mut ranked = (0..healths.len())
+        .filter(|i| !healths[*i].is_evicted(config))
+        .collect::<Vec<_>>();
+    ranked.sort_by_key(|i| {
+        let health = &healths[*i];
+        (
+            !health.is_healthy(config),
+            health.block_lag,
+            health.latency.unwrap_or(Duration::MAX),
+        )
+    });
+    ranked
+}
+
+#[cfg(test)]
+fn probed(endpoint: &str, latency_ms: u64, block_number: u64) -> EndpointHealth {
+    let mut health = EndpointHealth::new(endpoint.to_string());
+    health.record_success(
+        Duration::from_millis(latency_ms),
+        block_number,
+        1,
+        &HealthConfig::default(),
+    );
+    health
+}
+
+#[test]
+fn test_on_rank_endpoints() {
+    let config = HealthConfig {
+        chain_id: Some(1),
+        ..HealthConfig::default()
+    };
+
+    let mut failing = probed("failing", 1, 100);
+    failing.record_failure("timeout".to_string(), &config);
+    failing.record_failure("timeout".to_string(), &config);
+    let mut other_chain = probed("other_chain", 1, 100);
+    other_chain.chain_id = Some(5);
+
+    let mut healths = vec![
+        probed("slow", 80, 100),
+        probed("lagging", 5, 90),
+        failing,
+        probed("fast", 10, 100),
+        other_chain,
+        EndpointHealth::new("unprobed".to_string()),
+    ];
+    update_block_lag(&mut healths);
+
+    assert_eq!(healths[1].block_lag, 10);
+    assert!(!healths[1].is_healthy(&config));
+    assert!(healths[2].is_evicted(&config));
+    assert!(healths[4].is_evicted(&config));
+
+    let ranked = rank(&healths, &config)
+        .into_iter()
+        .map(|i| healths[i].endpoint.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(ranked, vec!["fast", "slow", "unprobed", "lagging"]);
+}
+
+#[test]
+fn test_on_error_window() {
+    let config = HealthConfig {
+        error_window: 4,
+        ..HealthConfig::default()
+    };
+    let mut health = EndpointHealth::new("node".to_string());
+    for _ in 0..4 {
+        health.record_failure("rate limited".to_string(), &config);
+    }
+    assert!(health.is_evicted(&config));
+
+    // Recovered nodes come back once the failures leave the window.
+    for _ in 0..3 {
+        health.record_success(Duration::from_millis(1), 1, 1, &config);
+    }
+    assert_eq!(health.error_rate(), 0.25);
+    assert!(health.is_healthy(&config));
+}
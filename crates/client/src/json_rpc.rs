@@ -1,14 +1,21 @@
-use crate::bundle_client::BundleParams;
-use anyhow::{Ok, Result};
-use serde::{Deserialize, Serialize};
+#[cfg(test)]
+use {
+    crate::bundle_client::BundleParams,
+    anyhow::{Ok, Result},
+};
 
 pub fn to_json_rpc(bundle_json: String) -> String {
+    to_json_rpc_method("eth_sendBundle", bundle_json)
+}
+
+/// A JSON-RPC request calling `method` with the params object as only param.
+pub fn to_json_rpc_method(method: &str, params_json: String) -> String {
     let request_body = format!(
-        r#"{{"id":1,"jsonrpc":"2.0","method":"eth_sendBundle","params":[{}]}}"#,
-        bundle_json
+        r#"{{"id":1,"jsonrpc":"2.0","method":"{}","params":[{}]}}"#,
+        method, params_json
     );
 
-    return request_body;
+    request_body
 }
 
 #[test]
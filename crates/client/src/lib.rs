@@ -1,10 +1,21 @@
+pub mod broadcast;
 pub mod builders;
 pub mod bundle_client;
+pub mod confirmation;
 pub mod erc20;
 pub mod erc721;
 pub mod ethereum_client;
+pub mod fee_oracle;
+pub mod health;
 pub mod json_rpc;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod mock_node;
 pub mod one_inch;
+pub mod resubmit;
+pub mod retry;
+pub mod simulator;
+pub mod transport;
+pub mod tx_builder;
 
 pub use builders::BlockBuilderEndpoint;
 pub use builders::Network;
@@ -0,0 +1,429 @@
+//! A local JSON-RPC node over http for tests.
+//!
+//! A request is answered by, in order: the replies queued with [`MockNode::push`], the reply
+//! set with [`MockNode::on`], the matching fixture, then the handler given to
+//! [`MockNode::spawn`]. `eth_sendBundle` is answered with a bundle hash when nothing else
+//! matches, so the node also stands in for a builder relay.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use account::{account::KeyOpt, Account};
+use anyhow::{anyhow, Result};
+use ethers::{
+    types::{Block, Bytes, Transaction, TransactionReceipt, H256, U256, U64},
+    utils::keccak256,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+/// What the node answers to a request.
+#[derive(Debug, Clone)]
+pub enum MockReply {
+    Result(Value),
+    Error {
+        code: i64,
+        message: String,
+    },
+    /// An http error status with a plain text body, like rate limiting proxies answer.
+    Status(u16),
+    /// A 200 answer with this body instead of a JSON-RPC response.
+    Body(Value),
+}
+
+impl MockReply {
+    pub fn result(value: impl serde::Serialize) -> Self {
+        MockReply::Result(serde_json::to_value(value).unwrap())
+    }
+
+    pub fn error(code: i64, message: &str) -> Self {
+        MockReply::Error {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// A request the node received.
+#[derive(Debug, Clone)]
+pub struct MockCall {
+    pub method: String,
+    pub params: Value,
+    /// The http headers, with lowercase names.
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+/// A recorded answer, matched when every field of `params` is in the request params.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Fixture {
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+    #[serde(default)]
+    pub result: Option<Value>,
+    #[serde(default)]
+    pub error: Option<FixtureError>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FixtureError {
+    pub code: i64,
+    pub message: String,
+}
+
+type Handler = Arc<dyn Fn(&str, &Value) -> MockReply + Send + Sync>;
+
+#[derive(Default)]
+struct MockState {
+    queued: HashMap<String, VecDeque<MockReply>>,
+    replies: HashMap<String, MockReply>,
+    fixtures: Vec<Fixture>,
+    handler: Option<Handler>,
+    calls: Vec<MockCall>,
+    delay: Duration,
+}
+
+impl MockState {
+    fn reply(&mut self, method: &str, params: &Value) -> MockReply {
+        if let Some(reply) = self.queued.get_mut(method).and_then(|q| q.pop_front()) {
+            return reply;
+        }
+        if let Some(reply) = self.replies.get(method) {
+            return reply.clone();
+        }
+        if let Some(fixture) = self
+            .fixtures
+            .iter()
+            .find(|f| f.method == method && json_contains(params, &f.params))
+        {
+            return match &fixture.error {
+                Some(e) => MockReply::error(e.code, &e.message),
+                None => MockReply::Result(fixture.result.clone().unwrap_or_default()),
+            };
+        }
+        if let Some(handler) = &self.handler {
+            return handler(method, params);
+        }
+        if method == "eth_sendBundle" {
+            let bundle_hash = H256(keccak256(params.to_string()));
+            return MockReply::result(json!({ "bundleHash": bundle_hash }));
+        }
+        MockReply::error(-32601, "method not found")
+    }
+}
+
+pub struct MockNode {
+    url: String,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockNode {
+    /// Start a node on a random local port, answering only what gets scripted.
+    pub async fn start() -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let state = Arc::new(Mutex::new(MockState::default()));
+
+        let node_state = Arc::clone(&state);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, Arc::clone(&node_state)));
+            }
+        });
+
+        Ok(Self { url, state })
+    }
+
+    /// Start a node answering with `handler(method, params)` what is not scripted.
+    pub async fn spawn(
+        handler: impl Fn(&str, &Value) -> MockReply + Send + Sync + 'static,
+    ) -> Result<Self> {
+        let node = Self::start().await?;
+        node.state.lock().unwrap().handler = Some(Arc::new(handler));
+        Ok(node)
+    }
+
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    /// Answer every request after `delay`, to simulate a slow node.
+    pub fn set_delay(&self, delay: Duration) {
+        self.state.lock().unwrap().delay = delay;
+    }
+
+    /// Answer every call of `method` with `reply`.
+    pub fn on(&self, method: &str, reply: MockReply) {
+        let mut state = self.state.lock().unwrap();
+        state.replies.insert(method.to_string(), reply);
+    }
+
+    /// Answer the next call of `method` with `reply`, before any other reply.
+    pub fn push(&self, method: &str, reply: MockReply) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .queued
+            .entry(method.to_string())
+            .or_default()
+            .push_back(reply);
+    }
+
+    /// Load fixtures from a JSON array of `{"method", "params", "result" | "error"}`.
+    pub fn load_fixtures(&self, fixtures_json: &str) -> Result<()> {
+        let fixtures: Vec<Fixture> = serde_json::from_str(fixtures_json)?;
+        self.state.lock().unwrap().fixtures.extend(fixtures);
+        Ok(())
+    }
+
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    pub fn calls_to(&self, method: &str) -> Vec<MockCall> {
+        self.calls()
+            .into_iter()
+            .filter(|call| call.method == method)
+            .collect()
+    }
+
+    pub fn assert_called(&self, method: &str, times: usize) {
+        let calls = self.calls_to(method).len();
+        assert_eq!(
+            calls, times,
+            "{} called {} times, expected {}",
+            method, calls, times
+        );
+    }
+}
+
+/// The key of the first anvil account, the sender of the test txs.
+pub const TEST_PRIVATE_KEY: &str =
+    "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+
+pub fn test_account() -> Account {
+    Account::new(KeyOpt::new_with_private_key(TEST_PRIVATE_KEY.to_string())).unwrap()
+}
+
+/// The chain a node of [`spawn_mock_chain`] answers from, change it to move the chain on.
+#[derive(Debug, Default)]
+pub struct MockChain {
+    pub head: u64,
+    /// The nonce of every account.
+    pub nonce: u64,
+    /// Whether the node knows the pending txs.
+    pub in_mempool: bool,
+    /// The mined tx and its block.
+    pub mined: Option<(H256, u64)>,
+    /// The raw txs the node accepted.
+    pub sent: Vec<Bytes>,
+    /// Reject the raw txs, like an underpriced replacement.
+    pub rejecting: bool,
+}
+
+/// Start a node answering the block, nonce, tx and receipt reads and the raw txs from `chain`.
+pub async fn spawn_mock_chain(chain: MockChain) -> (MockNode, Arc<Mutex<MockChain>>) {
+    let chain = Arc::new(Mutex::new(chain));
+    let node_chain = Arc::clone(&chain);
+    let node = MockNode::spawn(move |method, params| {
+        let mut chain = node_chain.lock().unwrap();
+        match method {
+            "eth_getBlockByNumber" => MockReply::result(Block::<H256> {
+                number: Some(chain.head.into()),
+                ..Default::default()
+            }),
+            "eth_getTransactionCount" => MockReply::result(U256::from(chain.nonce)),
+            "eth_sendRawTransaction" if chain.rejecting => {
+                MockReply::error(-32000, "replacement transaction underpriced")
+            }
+            "eth_sendRawTransaction" => {
+                let tx_bytes: Bytes = serde_json::from_value(params[0].clone()).unwrap();
+                let tx_hash = H256(keccak256(&tx_bytes));
+                chain.sent.push(tx_bytes);
+                MockReply::result(tx_hash)
+            }
+            "eth_getTransactionReceipt" => {
+                let tx_hash: H256 = serde_json::from_value(params[0].clone()).unwrap();
+                match chain.mined {
+                    Some((mined, block_number)) if mined == tx_hash => {
+                        MockReply::result(TransactionReceipt {
+                            transaction_hash: tx_hash,
+                            block_number: Some(block_number.into()),
+                            status: Some(U64::one()),
+                            ..Default::default()
+                        })
+                    }
+                    _ => MockReply::Result(Value::Null),
+                }
+            }
+            "eth_getTransactionByHash" if chain.in_mempool => MockReply::result(Transaction {
+                hash: serde_json::from_value(params[0].clone()).unwrap(),
+                ..Default::default()
+            }),
+            "eth_getTransactionByHash" => MockReply::Result(Value::Null),
+            _ => MockReply::error(-32601, "method not found"),
+        }
+    })
+    .await
+    .unwrap();
+    (node, chain)
+}
+
+/// Whether every field of `expected` is in `actual`, arrays may be longer than expected.
+fn json_contains(actual: &Value, expected: &Value) -> bool {
+    match (actual, expected) {
+        (_, Value::Null) => true,
+        (Value::Object(actual), Value::Object(expected)) => expected.iter().all(|(key, value)| {
+            actual
+                .get(key)
+                .is_some_and(|actual| json_contains(actual, value))
+        }),
+        (Value::Array(actual), Value::Array(expected)) => {
+            expected.len() <= actual.len()
+                && actual
+                    .iter()
+                    .zip(expected)
+                    .all(|(actual, expected)| json_contains(actual, expected))
+        }
+        (Value::String(actual), Value::String(expected)) => actual.eq_ignore_ascii_case(expected),
+        (actual, expected) => actual == expected,
+    }
+}
+
+async fn serve(mut stream: TcpStream, state: Arc<Mutex<MockState>>) -> Result<()> {
+    let (headers, body) = read_request(&mut stream).await?;
+    let req: Value = serde_json::from_str(&body)?;
+    let method = req["method"].as_str().unwrap_or_default().to_string();
+    let params = req["params"].clone();
+
+    let (reply, delay) = {
+        let mut state = state.lock().unwrap();
+        state.calls.push(MockCall {
+            method: method.clone(),
+            params: params.clone(),
+            headers,
+            body,
+        });
+        (state.reply(&method, &params), state.delay)
+    };
+
+    if !delay.is_zero() {
+        tokio::time::sleep(delay).await;
+    }
+
+    let (status, resp) = match reply {
+        MockReply::Result(result) => (
+            200,
+            json!({"jsonrpc": "2.0", "id": req["id"], "result": result}).to_string(),
+        ),
+        MockReply::Error { code, message } => (
+            200,
+            json!({"jsonrpc": "2.0", "id": req["id"], "error": {"code": code, "message": message}})
+                .to_string(),
+        ),
+        MockReply::Status(status) => (status, status_text(status).to_string()),
+        MockReply::Body(body) => (200, body.to_string()),
+    };
+
+    let http_resp = format!(
+        "HTTP/1.1 {} {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+        status,
+        status_text(status),
+        resp.len(),
+        resp
+    );
+    stream.write_all(http_resp.as_bytes()).await?;
+    Ok(())
+}
+
+fn status_text(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        429 => "Too Many Requests",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        _ => "Error",
+    }
+}
+
+async fn read_request(stream: &mut TcpStream) -> Result<(HashMap<String, String>, String)> {
+    let mut buf = vec![];
+    let mut chunk = [0u8; 4096];
+    loop {
+        let n = stream.read(&mut chunk).await?;
+        buf.extend_from_slice(&chunk[..n]);
+        let req = String::from_utf8_lossy(&buf).to_string();
+        if let Some((head, body)) = req.split_once("\r\n\r\n") {
+            let headers = head
+                .lines()
+                .skip(1)
+                .filter_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    Some((name.trim().to_lowercase(), value.trim().to_string()))
+                })
+                .collect::<HashMap<_, _>>();
+            let content_length = headers
+                .get("content-length")
+                .and_then(|value| value.parse::<usize>().ok())
+                .unwrap_or_default();
+            if body.len() >= content_length {
+                return Ok((headers, body.to_string()));
+            }
+        }
+        if n == 0 {
+            return Err(anyhow!("connection closed"));
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_on_mock_node_scripting() {
+    let node = MockNode::spawn(|_, _| MockReply::result("0x1"))
+        .await
+        .unwrap();
+    node.load_fixtures(r#"[{"method": "eth_call", "params": [{"to": "0xAB"}], "result": "0x2a"}]"#)
+        .unwrap();
+    node.on("eth_chainId", MockReply::result("0x5"));
+    node.push("eth_chainId", MockReply::error(-32005, "limit exceeded"));
+
+    let client = reqwest::Client::new();
+    let request = |method: &str, params: Value| {
+        let client = client.clone();
+        let url = node.url();
+        let body = json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params});
+        async move {
+            client
+                .post(url)
+                .json(&body)
+                .send()
+                .await
+                .unwrap()
+                .json::<Value>()
+                .await
+                .unwrap()
+        }
+    };
+
+    let resp = request("eth_chainId", json!([])).await;
+    assert_eq!(resp["error"]["code"], -32005);
+    assert_eq!(request("eth_chainId", json!([])).await["result"], "0x5");
+    let resp = request("eth_call", json!([{"to": "0xab", "data": "0x"}, "latest"])).await;
+    assert_eq!(resp["result"], "0x2a");
+    assert_eq!(request("eth_blockNumber", json!([])).await["result"], "0x1");
+
+    node.assert_called("eth_chainId", 2);
+    let call = &node.calls_to("eth_call")[0];
+    assert_eq!(call.params[1], "latest");
+    assert_eq!(call.headers["content-type"], "application/json");
+    assert!(call.body.contains(r#""method":"eth_call""#));
+    assert_eq!(node.calls().len(), 4);
+}
@@ -1,12 +1,12 @@
-use crate::erc20;
-use anyhow::Ok;
-use anyhow::Result;
 use ethers::contract::abigen;
-use ethers::middleware::SignerMiddleware;
-use ethers::providers::Http;
-use ethers::providers::Provider;
-use ethers::types::Address;
-use std::sync::Arc;
+#[cfg(test)]
+use {
+    crate::{erc20, mock_node::MockNode},
+    anyhow::{Ok, Result},
+    ethers::providers::{Http, Provider},
+    ethers::types::Address,
+    std::sync::Arc,
+};
 
 abigen!(
     IOneInch,
@@ -20,7 +20,10 @@ abigen!(
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
 async fn test_on_one_inch_oracle() -> Result<()> {
-    let provider_arc = Arc::new(Provider::<Http>::try_from("https://rpc.ankr.com/eth")?);
+    let node = MockNode::start().await?;
+    node.load_fixtures(include_str!("../fixtures/mainnet_calls.json"))?;
+
+    let provider_arc = Arc::new(Provider::<Http>::try_from(node.url())?);
     let rss3_token: Address = "0xc98d64da73a6616c42117b582e832812e7b8d57f".parse()?;
     let usdt_token: Address = "0xdAC17F958D2ee523a2206206994597C13D831ec7".parse()?;
     let one_inch_oracle_addr: Address = "0x0AdDd25a91563696D8567Df78D5A01C9a991F9B8".parse()?;
@@ -35,12 +38,9 @@ async fn test_on_one_inch_oracle() -> Result<()> {
         .call()
         .await?;
 
-    println!("usdt decimals: {}", usdt_decimals.clone());
-
-    println!(
-        "rss3 token price: {}",
-        price.as_u64() as f64 / 10_i32.pow(6) as f64
-    );
+    assert_eq!(usdt_decimals, 6);
+    // 0.12 USDT for one RSS3.
+    assert_eq!(price.as_u64(), 120_000);
 
     Ok(())
 }
@@ -0,0 +1,487 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use ethers::{
+    prelude::k256::ecdsa::SigningKey,
+    signers::Wallet,
+    types::{
+        transaction::eip2718::TypedTransaction, BlockNumber, Bytes, TransactionReceipt, H256, U256,
+    },
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    broadcast::BroadcastReport,
+    ethereum_client::{EthereumClientTrait, EthereumClients},
+};
+use account::{Account, AccountSigner};
+
+#[cfg(test)]
+use {
+    crate::mock_node::{spawn_mock_chain, test_account, MockChain},
+    ethers::types::{Address, Eip1559TransactionRequest},
+    ethers::utils::keccak256,
+};
+
+/// Nodes only accept a replacement paying at least 10% more on every fee.
+pub const MIN_REPLACEMENT_BUMP_PERCENT: u64 = 10;
+
+const CANCEL_GAS: u64 = 21_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResubmitConfig {
+    /// Blocks a tx may stay pending before it is replaced with higher fees.
+    pub bump_after_blocks: u64,
+    /// At least `MIN_REPLACEMENT_BUMP_PERCENT`.
+    pub bump_percent: u64,
+    /// The tx is left pending as is after this many bumps.
+    pub max_bumps: u32,
+}
+
+impl Default for ResubmitConfig {
+    fn default() -> Self {
+        Self {
+            bump_after_blocks: 3,
+            bump_percent: 12,
+            max_bumps: 5,
+        }
+    }
+}
+
+/// A nonce waiting to be mined and the txs sent for it, latest last.
+#[derive(Debug, Clone)]
+pub struct PendingTx {
+    pub tx: TypedTransaction,
+    pub tx_hashes: Vec<H256>,
+    pub sent_at_block: u64,
+    pub bumps: u32,
+}
+
+#[derive(Debug, Clone)]
+pub enum ResubmitEvent {
+    /// The tx was replaced with higher fees.
+    Bumped {
+        nonce: u64,
+        replaced_tx_hash: H256,
+        report: BroadcastReport,
+    },
+    /// One of the txs sent for the nonce got mined.
+    Mined {
+        nonce: u64,
+        receipt: Box<TransactionReceipt>,
+    },
+    /// The nonce got used by a tx that was not sent through the resubmitter.
+    NonceUsed { nonce: u64 },
+    /// No endpoint accepted the replacement, the tx stays pending as it was.
+    BumpRejected { nonce: u64, report: BroadcastReport },
+    /// Checking or bumping the tx failed, it stays pending as it was.
+    Failed { nonce: u64, error: String },
+}
+
+/// Return the tx with every fee raised by `bump_percent`, at least the replacement minimum.
+pub fn bump_fees(tx: &TypedTransaction, bump_percent: u64) -> Result<TypedTransaction> {
+    let bump_percent = bump_percent.max(MIN_REPLACEMENT_BUMP_PERCENT);
+    let bump = |fee: Option<U256>| -> Result<U256> {
+        let fee = fee.ok_or_else(|| anyhow!("tx without fees"))?;
+        // Rounded up so the bump never falls under the minimum.
+        Ok((fee * (100 + bump_percent) + 99) / 100)
+    };
+
+    let mut tx = tx.clone();
+    match &mut tx {
+        TypedTransaction::Eip1559(inner) => {
+            inner.max_fee_per_gas = Some(bump(inner.max_fee_per_gas)?);
+            inner.max_priority_fee_per_gas = Some(bump(inner.max_priority_fee_per_gas)?);
+        }
+        _ => {
+            let gas_price = bump(tx.gas_price())?;
+            tx.set_gas_price(gas_price);
+        }
+    }
+    Ok(tx)
+}
+
+/// A zero value transfer to itself with the nonce of `tx` and bumped fees, to cancel `tx`.
+pub fn cancel_tx(tx: &TypedTransaction, bump_percent: u64) -> Result<TypedTransaction> {
+    let from = *tx.from().ok_or_else(|| anyhow!("tx without from"))?;
+    let mut cancel = bump_fees(tx, bump_percent)?;
+    cancel.set_to(from);
+    cancel.set_value(U256::zero());
+    cancel.set_data(Bytes::new());
+    cancel.set_gas(CANCEL_GAS);
+    if let TypedTransaction::Eip2930(inner) = &mut cancel {
+        inner.access_list = Default::default();
+    }
+    if let TypedTransaction::Eip1559(inner) = &mut cancel {
+        inner.access_list = Default::default();
+    }
+    Ok(cancel)
+}
+
+/// Send txs through the pool and replace the ones stuck pending with higher fees.
+///
+/// Call [`Resubmitter::tick`] on every new block, or on a timer.
+pub struct Resubmitter<'a, S = Wallet<SigningKey>> {
+    clients: &'a EthereumClients,
+    account: &'a Account<S>,
+    config: ResubmitConfig,
+    pending: Mutex<BTreeMap<u64, PendingTx>>,
+}
+
+impl<'a, S: AccountSigner> Resubmitter<'a, S> {
+    pub fn new(clients: &'a EthereumClients, account: &'a Account<S>) -> Self {
+        Self {
+            clients,
+            account,
+            config: ResubmitConfig::default(),
+            pending: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn with_config(mut self, config: ResubmitConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub async fn pending(&self) -> Vec<PendingTx> {
+        self.pending.lock().await.values().cloned().collect()
+    }
+
+    /// Sign and broadcast a filled tx, and watch it until it is mined.
+    pub async fn send(&self, tx: TypedTransaction) -> Result<BroadcastReport> {
+        let mut tx = tx;
+        tx.set_from(self.account.address);
+        let nonce = tx
+            .nonce()
+            .ok_or_else(|| anyhow!("tx without nonce"))?
+            .as_u64();
+
+        let latest_block = self.latest_block().await?;
+        let (report, tx_hash) = self.sign_and_send(&tx).await?;
+        // The network never saw the tx, there is nothing to watch.
+        if !report.is_success() {
+            return Err(anyhow!(
+                "tx with nonce {} not accepted: {}",
+                nonce,
+                endpoint_errors(&report)
+            ));
+        }
+        self.pending.lock().await.insert(
+            nonce,
+            PendingTx {
+                tx,
+                tx_hashes: vec![tx_hash],
+                sent_at_block: latest_block,
+                bumps: 0,
+            },
+        );
+        Ok(report)
+    }
+
+    /// Replace the pending tx of `nonce` with a zero value transfer to itself.
+    pub async fn cancel(&self, nonce: u64) -> Result<BroadcastReport> {
+        let mut pending = self.pending.lock().await;
+        let pending_tx = pending
+            .get_mut(&nonce)
+            .ok_or_else(|| anyhow!("no pending tx with nonce {}", nonce))?;
+
+        let cancel = cancel_tx(&pending_tx.tx, self.config.bump_percent)?;
+        let latest_block = self.latest_block().await?;
+        let (report, tx_hash) = self.sign_and_send(&cancel).await?;
+        // Like a failed bump, the tx stays tracked as it was.
+        if !report.is_success() {
+            return Err(anyhow!(
+                "cancel of nonce {} not accepted: {}",
+                nonce,
+                endpoint_errors(&report)
+            ));
+        }
+        pending_tx.tx = cancel;
+        pending_tx.tx_hashes.push(tx_hash);
+        pending_tx.sent_at_block = latest_block;
+        Ok(report)
+    }
+
+    /// Check every pending tx once, forget the mined ones and bump the stale ones.
+    ///
+    /// A tx failing to be checked or bumped is reported as `Failed`, the other txs are still
+    /// checked.
+    pub async fn tick(&self) -> Result<Vec<ResubmitEvent>> {
+        let latest_block = self.latest_block().await?;
+        let chain_nonce = self.clients.get_nonce(self.account.address).await?.as_u64();
+
+        let mut events = vec![];
+        let mut pending = self.pending.lock().await;
+        let nonces = pending.keys().copied().collect::<Vec<_>>();
+        for nonce in nonces {
+            let pending_tx = pending.get_mut(&nonce).unwrap();
+            let event = match self
+                .check_pending(nonce, pending_tx, latest_block, chain_nonce)
+                .await
+            {
+                Ok(Some(event)) => event,
+                Ok(None) => continue,
+                Err(e) => ResubmitEvent::Failed {
+                    nonce,
+                    error: format!("{:#}", e),
+                },
+            };
+            if matches!(
+                event,
+                ResubmitEvent::Mined { .. } | ResubmitEvent::NonceUsed { .. }
+            ) {
+                pending.remove(&nonce);
+            }
+            events.push(event);
+        }
+
+        Ok(events)
+    }
+
+    async fn check_pending(
+        &self,
+        nonce: u64,
+        pending_tx: &mut PendingTx,
+        latest_block: u64,
+        chain_nonce: u64,
+    ) -> Result<Option<ResubmitEvent>> {
+        if let Some(receipt) = self.mined_receipt(pending_tx).await? {
+            return Ok(Some(ResubmitEvent::Mined {
+                nonce,
+                receipt: Box::new(receipt),
+            }));
+        }
+
+        if chain_nonce > nonce {
+            return Ok(Some(ResubmitEvent::NonceUsed { nonce }));
+        }
+
+        let stale = latest_block >= pending_tx.sent_at_block + self.config.bump_after_blocks;
+        if !stale || pending_tx.bumps >= self.config.max_bumps {
+            return Ok(None);
+        }
+
+        let bumped = bump_fees(&pending_tx.tx, self.config.bump_percent)?;
+        let (report, tx_hash) = self.sign_and_send(&bumped).await?;
+        if !report.is_success() {
+            return Ok(Some(ResubmitEvent::BumpRejected { nonce, report }));
+        }
+        let replaced_tx_hash = *pending_tx.tx_hashes.last().unwrap();
+        pending_tx.tx = bumped;
+        pending_tx.tx_hashes.push(tx_hash);
+        pending_tx.sent_at_block = latest_block;
+        pending_tx.bumps += 1;
+        Ok(Some(ResubmitEvent::Bumped {
+            nonce,
+            replaced_tx_hash,
+            report,
+        }))
+    }
+
+    async fn sign_and_send(&self, tx: &TypedTransaction) -> Result<(BroadcastReport, H256)> {
+        let report = self.clients.sign_and_send_tx(tx, self.account).await?;
+        let tx_hash = report
+            .tx_hash
+            .as_deref()
+            .ok_or_else(|| anyhow!("no tx hash in broadcast report"))?
+            .parse()?;
+        Ok((report, tx_hash))
+    }
+
+    /// Any of the txs sent for the nonce may be the one mined.
+    async fn mined_receipt(&self, pending_tx: &PendingTx) -> Result<Option<TransactionReceipt>> {
+        for tx_hash in pending_tx.tx_hashes.iter().rev() {
+            if let Some(receipt) = self.clients.get_receipt(*tx_hash).await? {
+                return Ok(Some(receipt));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn latest_block(&self) -> Result<u64> {
+        let block = self
+            .clients
+            .get_block(BlockNumber::Latest.into())
+            .await?
+            .and_then(|block| block.number)
+            .ok_or_else(|| anyhow!("latest block not found"))?;
+        Ok(block.as_u64())
+    }
+}
+
+/// The errors of the endpoints that did not accept the tx.
+fn endpoint_errors(report: &BroadcastReport) -> String {
+    report
+        .results
+        .iter()
+        .filter_map(|r| {
+            r.result
+                .as_ref()
+                .err()
+                .map(|e| format!("{}: {}", r.endpoint, e))
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[test]
+fn test_on_bump_fees() {
+    let tx: TypedTransaction = Eip1559TransactionRequest::new()
+        .max_fee_per_gas(100)
+        .max_priority_fee_per_gas(3)
+        .into();
+    let TypedTransaction::Eip1559(bumped) = bump_fees(&tx, 5).unwrap() else {
+        panic!("expected an eip1559 tx");
+    };
+    // 5% is raised to the 10% minimum, and rounded up.
+    assert_eq!(bumped.max_fee_per_gas, Some(110.into()));
+    assert_eq!(bumped.max_priority_fee_per_gas, Some(4.into()));
+
+    let legacy: TypedTransaction = ethers::types::TransactionRequest::new()
+        .gas_price(1_000)
+        .into();
+    assert_eq!(
+        bump_fees(&legacy, 25).unwrap().gas_price(),
+        Some(1_250.into())
+    );
+    assert!(bump_fees(&TypedTransaction::default(), 10).is_err());
+}
+
+#[tokio::test]
+async fn test_on_bump_and_cancel_stuck_tx() {
+    let (node, chain) = spawn_mock_chain(MockChain {
+        head: 10,
+        nonce: 5,
+        ..Default::default()
+    })
+    .await;
+
+    let clients = EthereumClients::new(vec![node.url()]).await.unwrap();
+    let account = test_account();
+    let resubmitter = Resubmitter::new(&clients, &account).with_config(ResubmitConfig {
+        bump_after_blocks: 2,
+        bump_percent: 10,
+        max_bumps: 5,
+    });
+
+    let tx: TypedTransaction = Eip1559TransactionRequest::new()
+        .to(Address::repeat_byte(1))
+        .value(1)
+        .nonce(5)
+        .gas(50_000)
+        .max_fee_per_gas(100)
+        .max_priority_fee_per_gas(10)
+        .chain_id(1)
+        .into();
+    assert!(resubmitter.send(tx).await.unwrap().is_success());
+
+    chain.lock().unwrap().head = 11;
+    assert!(resubmitter.tick().await.unwrap().is_empty());
+
+    chain.lock().unwrap().head = 12;
+    let events = resubmitter.tick().await.unwrap();
+    assert!(matches!(
+        events[..],
+        [ResubmitEvent::Bumped { nonce: 5, .. }]
+    ));
+    let (bumped, _, _) = account::verify::decode_signed_tx(&chain.lock().unwrap().sent[1]).unwrap();
+    let TypedTransaction::Eip1559(bumped) = bumped else {
+        panic!("expected an eip1559 tx");
+    };
+    assert_eq!(bumped.nonce, Some(5.into()));
+    assert_eq!(bumped.max_fee_per_gas, Some(110.into()));
+    assert_eq!(bumped.max_priority_fee_per_gas, Some(11.into()));
+
+    // A rejected bump or cancel leaves the bumped tx tracked.
+    {
+        let mut chain = chain.lock().unwrap();
+        chain.rejecting = true;
+        chain.head = 14;
+    }
+    let events = resubmitter.tick().await.unwrap();
+    assert!(matches!(
+        events[..],
+        [ResubmitEvent::BumpRejected { nonce: 5, .. }]
+    ));
+    assert!(resubmitter.cancel(5).await.is_err());
+    let pending = resubmitter.pending().await;
+    assert_eq!(pending[0].tx_hashes.len(), 2);
+    assert_eq!(pending[0].tx.to_addr(), Some(&Address::repeat_byte(1)));
+
+    chain.lock().unwrap().rejecting = false;
+    resubmitter.cancel(5).await.unwrap();
+    let cancel_bytes = chain.lock().unwrap().sent[2].clone();
+    let (cancel, _, signer) = account::verify::decode_signed_tx(&cancel_bytes).unwrap();
+    assert_eq!(cancel.to_addr(), Some(&signer));
+    assert_eq!(cancel.value(), Some(&U256::zero()));
+    assert_eq!(cancel.nonce(), Some(&5.into()));
+    assert_eq!(cancel.gas(), Some(&21_000.into()));
+
+    {
+        let mut chain = chain.lock().unwrap();
+        chain.mined = Some((H256(keccak256(&cancel_bytes)), chain.head));
+    }
+    let events = resubmitter.tick().await.unwrap();
+    assert!(matches!(
+        events[..],
+        [ResubmitEvent::Mined { nonce: 5, .. }]
+    ));
+    assert!(resubmitter.pending().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_on_tick_reports_failures_without_dropping_events() {
+    let (node, chain) = spawn_mock_chain(MockChain {
+        head: 10,
+        nonce: 5,
+        rejecting: true,
+        ..Default::default()
+    })
+    .await;
+
+    let clients = EthereumClients::new(vec![node.url()]).await.unwrap();
+    let account = test_account();
+    let resubmitter = Resubmitter::new(&clients, &account).with_config(ResubmitConfig {
+        bump_after_blocks: 2,
+        ..Default::default()
+    });
+
+    let tx: TypedTransaction = Eip1559TransactionRequest::new()
+        .to(Address::repeat_byte(1))
+        .nonce(5)
+        .gas(21_000)
+        .max_fee_per_gas(100)
+        .max_priority_fee_per_gas(10)
+        .chain_id(1)
+        .into();
+    // A tx no endpoint accepted is not tracked.
+    assert!(resubmitter.send(tx.clone()).await.is_err());
+    assert!(resubmitter.pending().await.is_empty());
+
+    chain.lock().unwrap().rejecting = false;
+    let report = resubmitter.send(tx).await.unwrap();
+    // Without fees the tx can not be bumped.
+    let unpriced: TypedTransaction = ethers::types::TransactionRequest::new()
+        .to(Address::repeat_byte(1))
+        .nonce(6)
+        .gas(21_000)
+        .chain_id(1)
+        .into();
+    resubmitter.send(unpriced).await.unwrap();
+
+    {
+        let mut chain = chain.lock().unwrap();
+        let tx_hash = report.tx_hash.unwrap().parse().unwrap();
+        chain.head = 12;
+        chain.mined = Some((tx_hash, 12));
+    }
+    let events = resubmitter.tick().await.unwrap();
+    assert!(matches!(
+        &events[..],
+        [ResubmitEvent::Mined { nonce: 5, .. }, ResubmitEvent::Failed { nonce: 6, error }]
+            if error.contains("tx without fees")
+    ));
+    let pending = resubmitter.pending().await;
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].tx.nonce(), Some(&6.into()));
+}
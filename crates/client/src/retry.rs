@@ -0,0 +1,255 @@
+use std::{future::Future, time::Duration};
+
+use anyhow::{anyhow, Result};
+use ethers::{
+    core::rand::{thread_rng, Rng},
+    providers::ProviderError,
+};
+use tokio::{sync::Mutex, time::Instant};
+
+/// How failed requests to an endpoint are retried.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt, 0 disables retrying.
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub multiplier: f64,
+    /// Each backoff is randomly moved by up to this fraction, so clients do not retry in lockstep.
+    pub jitter: f64,
+    /// Attempts slower than this fail with a retryable timeout.
+    pub request_timeout: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+            multiplier: 2.0,
+            jitter: 0.25,
+            request_timeout: Some(Duration::from_secs(10)),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn no_retry() -> Self {
+        Self {
+            max_retries: 0,
+            ..Self::default()
+        }
+    }
+
+    /// The backoff before the retry number `attempt`, starting at 0.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let backoff = self.initial_backoff.as_secs_f64() * self.multiplier.powi(attempt as i32);
+        let backoff = backoff.min(self.max_backoff.as_secs_f64());
+        let jitter = if self.jitter > 0.0 {
+            thread_rng().gen_range(-self.jitter..=self.jitter)
+        } else {
+            0.0
+        };
+        Duration::from_secs_f64((backoff * (1.0 + jitter)).max(0.0))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// Rate limits, timeouts and unreachable nodes, the same request may succeed later.
+    Retryable,
+    /// The node processed and rejected the request.
+    Fatal,
+}
+
+/// Classify a JSON-RPC error response.
+pub fn classify_json_rpc_error(code: i64, message: &str) -> ErrorClass {
+    match code {
+        // 429 from alchemy, -32005 limit exceeded from infura, -32016 rate limit from others.
+        429 | -32005 | -32016 => ErrorClass::Retryable,
+        _ if is_transient_message(message) => ErrorClass::Retryable,
+        _ => ErrorClass::Fatal,
+    }
+}
+
+/// Classify an error of a request to an endpoint.
+pub fn classify_error(err: &anyhow::Error) -> ErrorClass {
+    if err.downcast_ref::<tokio::time::error::Elapsed>().is_some() {
+        return ErrorClass::Retryable;
+    }
+
+    match err.downcast_ref::<ProviderError>() {
+        Some(ProviderError::JsonRpcClientError(e)) => match e.as_error_response() {
+            Some(e) => classify_json_rpc_error(e.code, &e.message),
+            None => classify_message(&e.to_string()),
+        },
+        Some(ProviderError::HTTPError(e)) => {
+            let status = e.status().map(|s| s.as_u16()).unwrap_or_default();
+            if e.is_timeout() || e.is_connect() || status == 429 || status >= 500 {
+                ErrorClass::Retryable
+            } else {
+                ErrorClass::Fatal
+            }
+        }
+        Some(e) => classify_message(&e.to_string()),
+        None => classify_message(&err.to_string()),
+    }
+}
+
+/// Transport errors only carry a message, the http status is lost in a non JSON body.
+fn classify_message(message: &str) -> ErrorClass {
+    let message = message.to_lowercase();
+    let unreachable = [
+        "connection",
+        "server has exited",
+        "channel",
+        "502",
+        "503",
+        "504",
+    ]
+    .iter()
+    .any(|pattern| message.contains(pattern));
+    if unreachable || is_transient_message(&message) {
+        ErrorClass::Retryable
+    } else {
+        ErrorClass::Fatal
+    }
+}
+
+fn is_transient_message(message: &str) -> bool {
+    let message = message.to_lowercase();
+    [
+        "429",
+        "too many requests",
+        "rate limit",
+        "limit exceeded",
+        "timed out",
+        "timeout",
+        "header not found",
+    ]
+    .iter()
+    .any(|pattern| message.contains(pattern))
+}
+
+/// Spaces the requests to an endpoint to stay under a requests per second limit.
+#[derive(Debug)]
+pub struct RateLimiter {
+    interval: Duration,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / requests_per_second.max(1),
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait for the next free slot.
+    pub async fn acquire(&self) {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().await;
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + self.interval;
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+/// Run `request` under the rate limit, retrying retryable errors with the policy.
+pub async fn retry<T, F, Fut>(
+    policy: &RetryPolicy,
+    rate_limiter: Option<&RateLimiter>,
+    mut request: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 0;
+    loop {
+        if let Some(rate_limiter) = rate_limiter {
+            rate_limiter.acquire().await;
+        }
+
+        let result = match policy.request_timeout {
+            Some(request_timeout) => tokio::time::timeout(request_timeout, request())
+                .await
+                .unwrap_or_else(|elapsed| Err(anyhow!(elapsed))),
+            None => request().await,
+        };
+
+        match result {
+            Err(e)
+                if attempt < policy.max_retries && classify_error(&e) == ErrorClass::Retryable =>
+            {
+                tokio::time::sleep(policy.backoff(attempt)).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[test]
+fn test_on_backoff() {
+    let policy = RetryPolicy {
+        jitter: 0.0,
+        ..RetryPolicy::default()
+    };
+    assert_eq!(policy.backoff(0), Duration::from_millis(200));
+    assert_eq!(policy.backoff(2), Duration::from_millis(800));
+    assert_eq!(policy.backoff(10), Duration::from_secs(5));
+
+    let policy = RetryPolicy::default();
+    for _ in 0..100 {
+        let backoff = policy.backoff(1);
+        assert!(backoff >= Duration::from_millis(300) && backoff <= Duration::from_millis(500));
+    }
+}
+
+#[test]
+fn test_on_classify_errors() {
+    assert_eq!(
+        classify_json_rpc_error(429, "Too Many Requests"),
+        ErrorClass::Retryable
+    );
+    assert_eq!(
+        classify_json_rpc_error(-32005, "limit exceeded"),
+        ErrorClass::Retryable
+    );
+    assert_eq!(
+        classify_json_rpc_error(-32000, "header not found"),
+        ErrorClass::Retryable
+    );
+    assert_eq!(
+        classify_json_rpc_error(-32000, "nonce too low"),
+        ErrorClass::Fatal
+    );
+    assert_eq!(
+        classify_json_rpc_error(3, "execution reverted"),
+        ErrorClass::Fatal
+    );
+    assert_eq!(
+        classify_error(&anyhow!("insufficient funds")),
+        ErrorClass::Fatal
+    );
+    assert_eq!(
+        classify_error(&anyhow!("error sending request: connection refused")),
+        ErrorClass::Retryable
+    );
+}
+
+#[tokio::test]
+async fn test_on_rate_limiter() {
+    let rate_limiter = RateLimiter::new(20);
+    let start = Instant::now();
+    for _ in 0..5 {
+        rate_limiter.acquire().await;
+    }
+    // The first request goes through right away.
+    assert!(start.elapsed() >= Duration::from_millis(200));
+}
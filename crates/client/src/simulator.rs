@@ -0,0 +1,827 @@
+//! Run txs and bundles against an in-memory EVM before sending them.
+//!
+//! The state comes from a [`StateSnapshot`] or is read from a node on demand with [`ForkDb`].
+//! Every simulated tx is committed, so the next one runs on top of it like in a block.
+
+use std::{collections::BTreeMap, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use ethers::{
+    abi::{self, ParamType},
+    providers::JsonRpcClient,
+    types::{
+        transaction::eip2718::TypedTransaction, Address, Block, BlockId, BlockNumber, Bytes, Log,
+        NameOrAddress, H256, U256,
+    },
+};
+use revm::{
+    db::{CacheDB, DatabaseRef, EmptyDB},
+    primitives::{
+        self as rp, AccountInfo, BlobExcessGasAndPrice, Bytecode, CreateScheme, ExecutionResult,
+        SpecId, TransactTo, TxEnv,
+    },
+    DatabaseCommit, Evm,
+};
+use serde::{Deserialize, Serialize};
+use tokio::runtime::{Handle, RuntimeFlavor};
+
+use crate::ethereum_client::{EthereumClient, EthereumClientTrait};
+
+#[cfg(test)]
+use {
+    crate::mock_node::{test_account, MockNode, MockReply},
+    ethers::{
+        abi::Token,
+        types::{Eip1559TransactionRequest, U64},
+        utils::hex,
+    },
+    serde_json::json,
+};
+
+/// `Error(string)`, the revert data of `require` and `revert("...")`.
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+/// `Panic(uint256)`, the revert data of failed asserts and arithmetic errors.
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+const SLOT_DURATION: u64 = 12;
+const DEFAULT_BLOCK_GAS_LIMIT: u64 = 30_000_000;
+
+/// An account in a [`StateSnapshot`], in the format of the `prestateTracer`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountState {
+    #[serde(default)]
+    pub balance: U256,
+    #[serde(default)]
+    pub nonce: u64,
+    #[serde(default)]
+    pub code: Bytes,
+    #[serde(default)]
+    pub storage: BTreeMap<H256, H256>,
+}
+
+/// The accounts a simulation starts from, the ones not in it are empty.
+pub type StateSnapshot = BTreeMap<Address, AccountState>;
+
+/// The block the txs are simulated in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimulationBlock {
+    pub number: u64,
+    pub timestamp: u64,
+    pub base_fee: U256,
+    pub gas_limit: U256,
+    pub coinbase: Address,
+    pub prevrandao: H256,
+    pub excess_blob_gas: u64,
+    /// The hard fork the EVM runs with.
+    pub spec_id: SpecId,
+}
+
+impl Default for SimulationBlock {
+    fn default() -> Self {
+        Self {
+            number: 0,
+            timestamp: 0,
+            base_fee: U256::zero(),
+            gas_limit: DEFAULT_BLOCK_GAS_LIMIT.into(),
+            coinbase: Address::zero(),
+            prevrandao: H256::zero(),
+            excess_blob_gas: 0,
+            spec_id: SpecId::CANCUN,
+        }
+    }
+}
+
+impl SimulationBlock {
+    /// The block following `parent`, where txs sent now would be included.
+    ///
+    /// The spec is the one of `parent`, set it when a hard fork activates at the next block.
+    pub fn next_after<TX>(parent: &Block<TX>) -> Self {
+        Self {
+            number: parent.number.unwrap_or_default().as_u64() + 1,
+            timestamp: parent.timestamp.as_u64() + SLOT_DURATION,
+            base_fee: parent.next_block_base_fee().unwrap_or_default(),
+            gas_limit: parent.gas_limit,
+            coinbase: parent.author.unwrap_or_default(),
+            prevrandao: parent.mix_hash.unwrap_or_default(),
+            excess_blob_gas: parent.excess_blob_gas.unwrap_or_default().as_u64(),
+            spec_id: spec_of(parent),
+        }
+    }
+}
+
+/// The latest hard fork the header fields of `block` tell apart.
+fn spec_of<TX>(block: &Block<TX>) -> SpecId {
+    if block.excess_blob_gas.is_some() {
+        SpecId::CANCUN
+    } else if block.withdrawals_root.is_some() {
+        SpecId::SHANGHAI
+    } else if block.base_fee_per_gas.is_some() && block.difficulty.is_zero() {
+        SpecId::MERGE
+    } else if block.base_fee_per_gas.is_some() {
+        SpecId::LONDON
+    } else {
+        SpecId::BERLIN
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecutionStatus {
+    Success {
+        output: Bytes,
+    },
+    /// Reverted, with the decoded `Error(string)` or `Panic(uint256)` if any.
+    Revert {
+        reason: Option<String>,
+        output: Bytes,
+    },
+    /// Stopped by the EVM, e.g. out of gas or an invalid opcode, spending all the gas.
+    Halt {
+        reason: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueChange<T> {
+    pub before: T,
+    pub after: T,
+}
+
+/// What a tx changed on an account.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccountDiff {
+    pub balance: Option<ValueChange<U256>>,
+    pub nonce: Option<ValueChange<u64>>,
+    /// The new code of a deployed contract.
+    pub code: Option<Bytes>,
+    pub storage: BTreeMap<H256, ValueChange<H256>>,
+}
+
+impl AccountDiff {
+    pub fn is_empty(&self) -> bool {
+        self.balance.is_none()
+            && self.nonce.is_none()
+            && self.code.is_none()
+            && self.storage.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TxSimulation {
+    /// Only known for signed txs.
+    pub tx_hash: Option<H256>,
+    pub from: Address,
+    pub gas_used: u64,
+    pub status: ExecutionStatus,
+    pub logs: Vec<Log>,
+    pub state_diff: BTreeMap<Address, AccountDiff>,
+}
+
+impl TxSimulation {
+    pub fn is_success(&self) -> bool {
+        matches!(self.status, ExecutionStatus::Success { .. })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BundleSimulation {
+    pub results: Vec<TxSimulation>,
+    pub gas_used: u64,
+}
+
+impl BundleSimulation {
+    pub fn is_success(&self) -> bool {
+        self.results.iter().all(TxSimulation::is_success)
+    }
+
+    /// The first tx that did not succeed.
+    pub fn first_failure(&self) -> Option<&TxSimulation> {
+        self.results.iter().find(|result| !result.is_success())
+    }
+}
+
+/// State read from a node at a fixed block, as the simulation touches it.
+///
+/// The EVM reads state synchronously, so requests block the current thread: simulations need
+/// a multi thread runtime. The reads go through the retry policy and rate limit of the client.
+pub struct ForkDb<M: JsonRpcClient> {
+    client: EthereumClient<M>,
+    block: BlockId,
+    handle: Handle,
+}
+
+impl<M: JsonRpcClient> ForkDb<M> {
+    pub fn new(client: EthereumClient<M>, block: BlockId) -> Result<Self> {
+        let handle = Handle::try_current()?;
+        check_runtime_flavor(&handle)?;
+        Ok(Self {
+            client,
+            block,
+            handle,
+        })
+    }
+
+    fn block_on<F: std::future::Future<Output = Result<T>>, T>(&self, f: F) -> Result<T> {
+        // `block_in_place` panics on a current thread runtime.
+        if let Ok(handle) = Handle::try_current() {
+            check_runtime_flavor(&handle)?;
+        }
+        tokio::task::block_in_place(|| self.handle.block_on(f))
+    }
+}
+
+fn check_runtime_flavor(handle: &Handle) -> Result<()> {
+    if handle.runtime_flavor() == RuntimeFlavor::CurrentThread {
+        return Err(anyhow!("simulating on a fork needs a multi thread runtime"));
+    }
+    Ok(())
+}
+
+impl<M: JsonRpcClient> DatabaseRef for ForkDb<M> {
+    type Error = anyhow::Error;
+
+    fn basic_ref(&self, address: rp::Address) -> Result<Option<AccountInfo>> {
+        let address = from_revm_address(address);
+        let block = Some(self.block);
+        let (balance, nonce, code) = self.block_on(async {
+            tokio::try_join!(
+                self.client.get_balance(address, block),
+                self.client.get_transaction_count(address, block),
+                self.client.get_code(address, block),
+            )
+        })?;
+
+        if balance.is_zero() && nonce.is_zero() && code.is_empty() {
+            return Ok(None);
+        }
+        let code = Bytecode::new_raw(code.to_vec().into());
+        Ok(Some(AccountInfo::new(
+            to_revm_u256(balance),
+            nonce.as_u64(),
+            code.hash_slow(),
+            code,
+        )))
+    }
+
+    fn code_by_hash_ref(&self, _code_hash: rp::B256) -> Result<Bytecode> {
+        // The code is loaded with the account in `basic_ref`.
+        Ok(Bytecode::new())
+    }
+
+    fn storage_ref(&self, address: rp::Address, index: rp::U256) -> Result<rp::U256> {
+        let value = self.block_on(self.client.get_storage_at(
+            from_revm_address(address),
+            H256(index.to_be_bytes()),
+            Some(self.block),
+        ))?;
+        Ok(rp::U256::from_be_bytes(value.0))
+    }
+
+    fn block_hash_ref(&self, number: rp::U256) -> Result<rp::B256> {
+        let number = u64::try_from(number).unwrap_or(u64::MAX);
+        let block = self.block_on(self.client.get_block(number.into()))?;
+        Ok(rp::B256::from(
+            block.and_then(|block| block.hash).unwrap_or_default().0,
+        ))
+    }
+}
+
+pub struct Simulator<DB: DatabaseRef> {
+    db: CacheDB<DB>,
+    block: SimulationBlock,
+    chain_id: u64,
+}
+
+impl Simulator<EmptyDB> {
+    pub fn from_snapshot(snapshot: &StateSnapshot, block: SimulationBlock, chain_id: u64) -> Self {
+        let mut db = CacheDB::new(EmptyDB::default());
+        for (address, account) in snapshot {
+            let address = to_revm_address(*address);
+            let code = Bytecode::new_raw(account.code.to_vec().into());
+            db.insert_account_info(
+                address,
+                AccountInfo::new(
+                    to_revm_u256(account.balance),
+                    account.nonce,
+                    code.hash_slow(),
+                    code,
+                ),
+            );
+            for (slot, value) in &account.storage {
+                // Only fails to load the account from the external db, which is empty.
+                let _ = db.insert_account_storage(
+                    address,
+                    rp::U256::from_be_bytes(slot.0),
+                    rp::U256::from_be_bytes(value.0),
+                );
+            }
+        }
+
+        Self {
+            db,
+            block,
+            chain_id,
+        }
+    }
+
+    /// Load a snapshot from JSON, e.g. a `prestateTracer` result.
+    pub fn from_snapshot_json(
+        snapshot_json: &str,
+        block: SimulationBlock,
+        chain_id: u64,
+    ) -> Result<Self> {
+        let snapshot: StateSnapshot = serde_json::from_str(snapshot_json)?;
+        Ok(Self::from_snapshot(&snapshot, block, chain_id))
+    }
+}
+
+impl<M: JsonRpcClient + 'static> Simulator<ForkDb<M>> {
+    /// Fork the chain at the end of `block`, latest by default, to simulate the next block.
+    pub async fn fork(client: &EthereumClient<M>, block: Option<BlockId>) -> Result<Self> {
+        let block = block.unwrap_or(BlockId::Number(BlockNumber::Latest));
+        let parent = client
+            .get_block(block)
+            .await?
+            .ok_or_else(|| anyhow!("block {:?} not found", block))?;
+        let parent_number = parent
+            .number
+            .ok_or_else(|| anyhow!("block {:?} is pending", block))?;
+        let chain_id = client.chain_id().await?;
+
+        let db = ForkDb::new(client.clone(), parent_number.into())?;
+        Ok(Self {
+            db: CacheDB::new(db),
+            block: SimulationBlock::next_after(&parent),
+            chain_id: chain_id.as_u64(),
+        })
+    }
+}
+
+impl<DB: DatabaseRef> Simulator<DB>
+where
+    DB::Error: std::fmt::Debug,
+{
+    pub fn block(&self) -> &SimulationBlock {
+        &self.block
+    }
+
+    pub fn set_block(&mut self, block: SimulationBlock) {
+        self.block = block;
+    }
+
+    /// Simulate an unsigned tx sent by its `from`, fees default to the base fee.
+    pub fn simulate_tx(&mut self, tx: &TypedTransaction) -> Result<TxSimulation> {
+        let from = *tx.from().ok_or_else(|| anyhow!("tx without from"))?;
+        self.transact(tx, from, None)
+    }
+
+    /// Simulate a signed raw tx, as sent with `send_raw_tx` or in a bundle.
+    pub fn simulate_raw_tx(&mut self, raw_tx: &Bytes) -> Result<TxSimulation> {
+        let (tx, signature, from) = account::verify::decode_signed_tx(raw_tx)?;
+        self.transact(&tx, from, Some(tx.hash(&signature)))
+    }
+
+    /// Simulate the signed raw txs of a bundle in order, as given to `BundleClient::send_bundle`.
+    ///
+    /// A tx that reverts is reported in the results, one that is invalid, e.g. with a wrong
+    /// nonce or not enough balance for its gas, is an error like relays reject the bundle.
+    pub fn simulate_bundle(&mut self, raw_txns: &[String]) -> Result<BundleSimulation> {
+        let mut bundle = BundleSimulation::default();
+        for raw_tx in raw_txns {
+            let raw_tx = Bytes::from_str(raw_tx)?;
+            let result = self.simulate_raw_tx(&raw_tx)?;
+            bundle.gas_used += result.gas_used;
+            bundle.results.push(result);
+        }
+        Ok(bundle)
+    }
+
+    fn transact(
+        &mut self,
+        tx: &TypedTransaction,
+        from: Address,
+        tx_hash: Option<H256>,
+    ) -> Result<TxSimulation> {
+        let tx_env = self.tx_env(tx, from);
+        let block = &self.block;
+        let result = Evm::builder()
+            .with_db(&mut self.db)
+            .with_spec_id(block.spec_id)
+            .modify_cfg_env(|cfg| cfg.chain_id = self.chain_id)
+            .modify_block_env(|env| {
+                env.number = rp::U256::from(block.number);
+                env.timestamp = rp::U256::from(block.timestamp);
+                env.basefee = to_revm_u256(block.base_fee);
+                env.gas_limit = to_revm_u256(block.gas_limit);
+                env.coinbase = to_revm_address(block.coinbase);
+                env.prevrandao = Some(rp::B256::from(block.prevrandao.0));
+                env.blob_excess_gas_and_price =
+                    Some(BlobExcessGasAndPrice::new(block.excess_blob_gas));
+            })
+            .with_tx_env(tx_env)
+            .build()
+            .transact()
+            .map_err(|e| anyhow!("invalid tx from {:?}: {:?}", from, e))?;
+        let state_diff = self.state_diff(&result.state);
+        self.db.commit(result.state);
+
+        let (gas_used, status, logs) = match result.result {
+            ExecutionResult::Success {
+                gas_used,
+                logs,
+                output,
+                ..
+            } => (
+                gas_used,
+                ExecutionStatus::Success {
+                    output: output.into_data().to_vec().into(),
+                },
+                logs,
+            ),
+            ExecutionResult::Revert { gas_used, output } => (
+                gas_used,
+                ExecutionStatus::Revert {
+                    reason: decode_revert_reason(&output),
+                    output: output.to_vec().into(),
+                },
+                vec![],
+            ),
+            ExecutionResult::Halt { reason, gas_used } => (
+                gas_used,
+                ExecutionStatus::Halt {
+                    reason: format!("{:?}", reason),
+                },
+                vec![],
+            ),
+        };
+
+        Ok(TxSimulation {
+            tx_hash,
+            from,
+            gas_used,
+            status,
+            logs: logs
+                .into_iter()
+                .map(|log| Log {
+                    address: from_revm_address(log.address),
+                    topics: log.topics().iter().map(|topic| H256(topic.0)).collect(),
+                    data: log.data.data.to_vec().into(),
+                    transaction_hash: tx_hash,
+                    ..Default::default()
+                })
+                .collect(),
+            state_diff,
+        })
+    }
+
+    fn tx_env(&self, tx: &TypedTransaction, from: Address) -> TxEnv {
+        let (gas_price, gas_priority_fee) = match tx {
+            TypedTransaction::Eip1559(inner) => (
+                inner.max_fee_per_gas.unwrap_or(self.block.base_fee),
+                Some(inner.max_priority_fee_per_gas.unwrap_or_default()),
+            ),
+            _ => (tx.gas_price().unwrap_or(self.block.base_fee), None),
+        };
+        let transact_to = match tx.to() {
+            Some(NameOrAddress::Address(to)) => TransactTo::Call(to_revm_address(*to)),
+            _ => TransactTo::Create(CreateScheme::Create),
+        };
+        let access_list = tx
+            .access_list()
+            .map(|access_list| {
+                access_list
+                    .0
+                    .iter()
+                    .map(|item| {
+                        (
+                            to_revm_address(item.address),
+                            item.storage_keys
+                                .iter()
+                                .map(|key| rp::U256::from_be_bytes(key.0))
+                                .collect(),
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        TxEnv {
+            caller: to_revm_address(from),
+            gas_limit: tx
+                .gas()
+                .copied()
+                .unwrap_or(self.block.gas_limit)
+                .min(self.block.gas_limit)
+                .as_u64(),
+            gas_price: to_revm_u256(gas_price),
+            gas_priority_fee: gas_priority_fee.map(to_revm_u256),
+            transact_to,
+            value: to_revm_u256(tx.value().copied().unwrap_or_default()),
+            data: tx
+                .data()
+                .map(|data| data.to_vec())
+                .unwrap_or_default()
+                .into(),
+            nonce: tx.nonce().map(|nonce| nonce.as_u64()),
+            chain_id: tx.chain_id().map(|chain_id| chain_id.as_u64()),
+            access_list,
+            ..Default::default()
+        }
+    }
+
+    /// Compare the accounts the tx touched with the state before it.
+    fn state_diff(&self, state: &rp::State) -> BTreeMap<Address, AccountDiff> {
+        let mut diffs = BTreeMap::new();
+        for (address, account) in state {
+            if !account.is_touched() {
+                continue;
+            }
+            let before = self
+                .db
+                .basic_ref(*address)
+                .ok()
+                .flatten()
+                .unwrap_or_default();
+            let after = &account.info;
+
+            let mut diff = AccountDiff::default();
+            if before.balance != after.balance {
+                diff.balance = Some(ValueChange {
+                    before: from_revm_u256(before.balance),
+                    after: from_revm_u256(after.balance),
+                });
+            }
+            if before.nonce != after.nonce {
+                diff.nonce = Some(ValueChange {
+                    before: before.nonce,
+                    after: after.nonce,
+                });
+            }
+            if before.code_hash != after.code_hash {
+                diff.code = after
+                    .code
+                    .as_ref()
+                    .map(|code| code.original_bytes().to_vec().into());
+            }
+            for (slot, value) in &account.storage {
+                if value.previous_or_original_value != value.present_value {
+                    diff.storage.insert(
+                        H256(slot.to_be_bytes()),
+                        ValueChange {
+                            before: H256(value.previous_or_original_value.to_be_bytes()),
+                            after: H256(value.present_value.to_be_bytes()),
+                        },
+                    );
+                }
+            }
+
+            if !diff.is_empty() {
+                diffs.insert(from_revm_address(*address), diff);
+            }
+        }
+        diffs
+    }
+}
+
+/// Decode the reason of a revert from `Error(string)` or `Panic(uint256)` data.
+pub fn decode_revert_reason(output: &[u8]) -> Option<String> {
+    if output.len() < 4 {
+        return None;
+    }
+    let (selector, data) = output.split_at(4);
+    if selector == ERROR_SELECTOR {
+        let mut tokens = abi::decode(&[ParamType::String], data).ok()?;
+        return tokens.pop()?.into_string();
+    }
+    if selector == PANIC_SELECTOR {
+        let mut tokens = abi::decode(&[ParamType::Uint(256)], data).ok()?;
+        return Some(format!("panic code {:#x}", tokens.pop()?.into_uint()?));
+    }
+    None
+}
+
+fn to_revm_address(address: Address) -> rp::Address {
+    rp::Address::from(address.0)
+}
+
+fn from_revm_address(address: rp::Address) -> Address {
+    Address::from(address.into_array())
+}
+
+fn to_revm_u256(value: U256) -> rp::U256 {
+    rp::U256::from_limbs(value.0)
+}
+
+fn from_revm_u256(value: rp::U256) -> U256 {
+    U256(value.into_limbs())
+}
+
+#[cfg(test)]
+fn test_tx(from: Address, to: Address, nonce: u64, data: Vec<u8>) -> TypedTransaction {
+    Eip1559TransactionRequest::new()
+        .from(from)
+        .to(to)
+        .nonce(nonce)
+        .gas(100_000)
+        .max_fee_per_gas(2_000_000_000u64)
+        .max_priority_fee_per_gas(1_000_000_000u64)
+        .chain_id(1)
+        .data(data)
+        .into()
+}
+
+#[tokio::test]
+async fn test_on_simulate_bundle_on_snapshot() {
+    let account = test_account();
+    // Store the calldata word at slot 0 and log it.
+    let store = Address::repeat_byte(0xaa);
+    let store_code = hex::decode("6000358060005560005260206000a000").unwrap();
+    // Revert with `Error("nope")`, copied from the code after the 12 bytes of the prefix.
+    let revert = Address::repeat_byte(0xbb);
+    let revert_code = [
+        hex::decode("6064600c60003960646000fd").unwrap(),
+        ERROR_SELECTOR.to_vec(),
+        abi::encode(&[Token::String("nope".to_string())]),
+    ]
+    .concat();
+
+    let snapshot = StateSnapshot::from([
+        (
+            account.address,
+            AccountState {
+                balance: U256::exp10(18),
+                ..Default::default()
+            },
+        ),
+        (
+            store,
+            AccountState {
+                code: store_code.into(),
+                storage: BTreeMap::from([(H256::zero(), H256::from_low_u64_be(1))]),
+                ..Default::default()
+            },
+        ),
+        (
+            revert,
+            AccountState {
+                code: revert_code.into(),
+                ..Default::default()
+            },
+        ),
+    ]);
+    let block = SimulationBlock {
+        number: 100,
+        base_fee: 1_000_000_000u64.into(),
+        coinbase: Address::repeat_byte(0xcc),
+        ..Default::default()
+    };
+    let mut simulator = Simulator::from_snapshot(&snapshot, block, 1);
+
+    let word = H256::from_low_u64_be(42);
+    let raw_txns = [
+        account
+            .sign_tx(&test_tx(account.address, store, 0, word.0.to_vec()))
+            .await
+            .unwrap(),
+        account
+            .sign_tx(&test_tx(account.address, revert, 1, vec![]))
+            .await
+            .unwrap(),
+    ];
+    let bundle = simulator
+        .simulate_bundle(&raw_txns.iter().map(|tx| tx.to_string()).collect::<Vec<_>>())
+        .unwrap();
+
+    assert!(!bundle.is_success());
+    let (stored, reverted) = (&bundle.results[0], &bundle.results[1]);
+    assert_eq!(bundle.first_failure().unwrap().tx_hash, reverted.tx_hash);
+    assert_eq!(bundle.gas_used, stored.gas_used + reverted.gas_used);
+
+    assert!(stored.is_success());
+    assert_eq!(stored.from, account.address);
+    assert_eq!(stored.logs.len(), 1);
+    assert_eq!(stored.logs[0].address, store);
+    assert_eq!(stored.logs[0].data.to_vec(), word.0.to_vec());
+    assert_eq!(
+        stored.state_diff[&store].storage[&H256::zero()],
+        ValueChange {
+            before: H256::from_low_u64_be(1),
+            after: word,
+        }
+    );
+    let sender = &stored.state_diff[&account.address];
+    assert_eq!(
+        sender.nonce,
+        Some(ValueChange {
+            before: 0,
+            after: 1
+        })
+    );
+    // The sender pays the base fee and the tip, the coinbase gets the tip.
+    let fee = U256::from(stored.gas_used) * 2_000_000_000u64;
+    let balance = sender.balance.as_ref().unwrap();
+    assert_eq!(balance.before - balance.after, fee);
+    assert_eq!(
+        stored.state_diff[&Address::repeat_byte(0xcc)].balance,
+        Some(ValueChange {
+            before: U256::zero(),
+            after: U256::from(stored.gas_used) * 1_000_000_000u64,
+        })
+    );
+
+    assert_eq!(
+        reverted.status,
+        ExecutionStatus::Revert {
+            reason: Some("nope".to_string()),
+            output: revert_code_output(),
+        }
+    );
+    assert!(reverted.logs.is_empty());
+    // The nonce of the second tx is only valid on top of the first one.
+    assert_eq!(
+        reverted.state_diff[&account.address].nonce,
+        Some(ValueChange {
+            before: 1,
+            after: 2
+        })
+    );
+
+    // Replaying the committed nonce is invalid, like a relay would reject it.
+    let replay = simulator.simulate_raw_tx(&raw_txns[0]);
+    assert!(replay.is_err());
+
+    let call = simulator
+        .simulate_tx(&test_tx(account.address, store, 2, vec![]))
+        .unwrap();
+    assert!(call.is_success());
+    assert_eq!(call.tx_hash, None);
+}
+
+#[cfg(test)]
+fn revert_code_output() -> Bytes {
+    [
+        ERROR_SELECTOR.to_vec(),
+        abi::encode(&[Token::String("nope".to_string())]),
+    ]
+    .concat()
+    .into()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_on_simulate_on_fork() {
+    let sender = test_account().address;
+    let node = MockNode::spawn(move |method, params| match method {
+        "eth_chainId" => MockReply::result(U64::from(1)),
+        "eth_getBlockByNumber" => MockReply::result(json!({
+            "number": "0x10",
+            "hash": H256::repeat_byte(0x10),
+            "parentHash": H256::repeat_byte(0x0f),
+            "timestamp": "0x64",
+            "gasLimit": "0x1c9c380",
+            "gasUsed": "0xe4e1c0",
+            "baseFeePerGas": "0x3b9aca00",
+            "miner": Address::repeat_byte(0xcc),
+            "mixHash": H256::repeat_byte(0x01),
+            "transactions": [],
+        })),
+        "eth_getBalance" if params[0] == json!(sender) => MockReply::result(U256::exp10(18)),
+        "eth_getBalance" | "eth_getTransactionCount" => MockReply::result(U256::zero()),
+        "eth_getCode" => MockReply::result("0x"),
+        _ => MockReply::error(-32601, "method not found"),
+    })
+    .await
+    .unwrap();
+    let client = EthereumClient::new(node.url()).unwrap();
+
+    let mut simulator = Simulator::fork(&client, None).await.unwrap();
+    assert_eq!(simulator.block().number, 0x11);
+    assert_eq!(simulator.block().timestamp, 0x64 + SLOT_DURATION);
+    // Half full parent, the base fee stays.
+    assert_eq!(simulator.block().base_fee, 1_000_000_000u64.into());
+    assert_eq!(simulator.block().spec_id, SpecId::MERGE);
+
+    let receiver = Address::repeat_byte(0xdd);
+    let mut transfer = test_tx(sender, receiver, 0, vec![]);
+    transfer.set_value(U256::exp10(17));
+    let raw_tx = test_account().sign_tx(&transfer).await.unwrap();
+
+    let bundle = simulator.simulate_bundle(&[raw_tx.to_string()]).unwrap();
+    assert!(bundle.is_success());
+    assert_eq!(bundle.gas_used, 21_000);
+    assert_eq!(
+        bundle.results[0].state_diff[&receiver].balance,
+        Some(ValueChange {
+            before: U256::zero(),
+            after: U256::exp10(17),
+        })
+    );
+    // State is read at the forked block.
+    let balance_call = &node.calls_to("eth_getBalance")[0];
+    assert_eq!(balance_call.params[1], "0x10");
+}
+
+#[tokio::test]
+async fn test_on_fork_needs_multi_thread_runtime() {
+    let client = EthereumClient::new("http://localhost:8545".to_string()).unwrap();
+    let fork = ForkDb::new(client, BlockNumber::Latest.into());
+    assert!(fork.is_err());
+}
@@ -0,0 +1,388 @@
+use std::{fmt, path::PathBuf};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use ethers::providers::{
+    Http, HttpClientError, Ipc, IpcError, JsonRpcClient, JsonRpcError, ProviderError, RpcError, Ws,
+    WsClientError,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+use tokio::sync::RwLock;
+
+#[cfg(test)]
+use {
+    ethers::{
+        providers::{Middleware, Provider},
+        types::Address,
+    },
+    futures::{SinkExt, StreamExt},
+    tokio::net::TcpListener,
+    tokio_tungstenite::tungstenite::Message,
+};
+#[cfg(all(test, unix))]
+use {
+    std::time::Duration,
+    tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::UnixListener,
+    },
+};
+
+/// Requests the node may have acted on before the connection dropped, and would act on again
+/// if replayed: without a nonce, the node signs and sends another tx.
+const NOT_REPLAYED_METHODS: [&str; 2] = ["eth_sendTransaction", "personal_sendTransaction"];
+
+/// A JSON-RPC transport picked from the endpoint scheme, so http, websocket and IPC
+/// endpoints can live in the same pool.
+///
+/// - `http://`, `https://`
+/// - `ws://`, `wss://`, reconnects on disconnect
+/// - `ipc://<path>` or a path to a `.ipc` socket, reconnects on disconnect
+///
+/// Both websocket and IPC requests are sent again after a reconnect, except
+/// `NOT_REPLAYED_METHODS` which fail with the disconnect error.
+#[derive(Debug)]
+pub enum Transport {
+    Http(Http),
+    Ws(ReconnectingWs),
+    Ipc(ReconnectingIpc),
+}
+
+impl Transport {
+    pub async fn connect(endpoint: &str) -> Result<Self> {
+        if endpoint.starts_with("http://") || endpoint.starts_with("https://") {
+            return Ok(Transport::Http(endpoint.parse::<Http>()?));
+        }
+
+        if endpoint.starts_with("ws://") || endpoint.starts_with("wss://") {
+            let ws = ReconnectingWs::connect(endpoint.to_string()).await?;
+            return Ok(Transport::Ws(ws));
+        }
+
+        if let Some(path) = ipc_path(endpoint) {
+            return Ok(Transport::Ipc(ReconnectingIpc::connect(path).await?));
+        }
+
+        Err(anyhow!("unsupported endpoint: {}", endpoint))
+    }
+}
+
+fn ipc_path(endpoint: &str) -> Option<PathBuf> {
+    if let Some(path) = endpoint.strip_prefix("ipc://") {
+        return Some(PathBuf::from(path));
+    }
+    if endpoint.ends_with(".ipc") {
+        return Some(PathBuf::from(endpoint));
+    }
+    None
+}
+
+#[async_trait]
+impl JsonRpcClient for Transport {
+    type Error = TransportError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: fmt::Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        match self {
+            Transport::Http(http) => Ok(http.request(method, params).await?),
+            Transport::Ws(ws) => Ok(ws.request(method, params).await?),
+            Transport::Ipc(ipc) => Ok(ipc.request(method, params).await?),
+        }
+    }
+}
+
+/// A connection [`Reconnecting`] can open again.
+#[async_trait]
+pub trait Reconnect: JsonRpcClient + Clone {
+    type Target: fmt::Debug + Send + Sync;
+
+    async fn open(target: &Self::Target) -> Result<Self, Self::Error>;
+
+    /// The node went away, the request may or may not have reached it.
+    fn is_disconnected(err: &Self::Error) -> bool;
+}
+
+#[async_trait]
+impl Reconnect for Ipc {
+    type Target = PathBuf;
+
+    async fn open(path: &PathBuf) -> Result<Self, IpcError> {
+        Ipc::connect(path).await
+    }
+
+    fn is_disconnected(err: &IpcError) -> bool {
+        matches!(
+            err,
+            IpcError::IoError(_)
+                | IpcError::ChannelError(_)
+                | IpcError::RequestCancelled(_)
+                | IpcError::ServerExit
+        )
+    }
+}
+
+#[async_trait]
+impl Reconnect for Ws {
+    type Target = String;
+
+    /// Without the reconnects of ethers, which would send every request again.
+    async fn open(url: &String) -> Result<Self, WsClientError> {
+        Ws::connect_with_reconnects(url, 0).await
+    }
+
+    fn is_disconnected(err: &WsClientError) -> bool {
+        matches!(
+            err,
+            WsClientError::InternalError(_)
+                | WsClientError::UnexpectedClose
+                | WsClientError::DeadChannel
+                | WsClientError::TooManyReconnects
+        )
+    }
+}
+
+pub type ReconnectingIpc = Reconnecting<Ipc>;
+pub type ReconnectingWs = Reconnecting<Ws>;
+
+/// A connection that connects again when the node went away.
+#[derive(Debug)]
+pub struct Reconnecting<C: Reconnect> {
+    target: C::Target,
+    /// The connection and how many times it was opened.
+    conn: RwLock<(u64, C)>,
+}
+
+impl<C: Reconnect> Reconnecting<C>
+where
+    C::Error: Into<anyhow::Error>,
+{
+    pub async fn connect(target: C::Target) -> Result<Self> {
+        let conn = C::open(&target).await.map_err(Into::into)?;
+        Ok(Self {
+            target,
+            conn: RwLock::new((0, conn)),
+        })
+    }
+}
+
+impl<C: Reconnect> Reconnecting<C> {
+    async fn reconnect(&self, stale_generation: u64) -> Result<C, C::Error> {
+        let mut conn = self.conn.write().await;
+        // Another request reconnected in the meantime.
+        if conn.0 != stale_generation {
+            return Ok(conn.1.clone());
+        }
+        *conn = (stale_generation + 1, C::open(&self.target).await?);
+        Ok(conn.1.clone())
+    }
+}
+
+#[async_trait]
+impl<C: Reconnect> JsonRpcClient for Reconnecting<C>
+where
+    C::Error: From<serde_json::Error>,
+{
+    type Error = C::Error;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: fmt::Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        // Kept as a value to send it again after reconnecting.
+        let params = serde_json::to_value(params)?;
+        let (generation, conn) = self.conn.read().await.clone();
+        match conn.request(method, params.clone()).await {
+            Err(e) if C::is_disconnected(&e) => {
+                let conn = self.reconnect(generation).await?;
+                if NOT_REPLAYED_METHODS.contains(&method) {
+                    return Err(e);
+                }
+                conn.request(method, params).await
+            }
+            result => result,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum TransportError {
+    #[error(transparent)]
+    Http(#[from] HttpClientError),
+    #[error(transparent)]
+    Ws(#[from] WsClientError),
+    #[error(transparent)]
+    Ipc(#[from] IpcError),
+}
+
+impl RpcError for TransportError {
+    fn as_error_response(&self) -> Option<&JsonRpcError> {
+        match self {
+            TransportError::Http(e) => e.as_error_response(),
+            TransportError::Ws(e) => e.as_error_response(),
+            TransportError::Ipc(e) => e.as_error_response(),
+        }
+    }
+
+    fn as_serde_error(&self) -> Option<&serde_json::Error> {
+        match self {
+            TransportError::Http(e) => e.as_serde_error(),
+            TransportError::Ws(e) => e.as_serde_error(),
+            TransportError::Ipc(e) => e.as_serde_error(),
+        }
+    }
+}
+
+impl From<TransportError> for ProviderError {
+    fn from(src: TransportError) -> Self {
+        ProviderError::JsonRpcClientError(Box::new(src))
+    }
+}
+
+/// Answer `0x1` over a unix socket, a flaky node closes every other connection after one
+/// answer.
+#[cfg(all(test, unix))]
+pub(crate) fn spawn_ipc_node(path: PathBuf, flaky: bool) {
+    let listener = UnixListener::bind(path).unwrap();
+    tokio::spawn(async move {
+        let mut connections = 0;
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            connections += 1;
+            let close_after_answer = flaky && connections % 2 == 1;
+            tokio::spawn(async move {
+                let mut buf = vec![];
+                let mut chunk = [0u8; 4096];
+                loop {
+                    let n = stream.read(&mut chunk).await.unwrap_or(0);
+                    if n == 0 {
+                        return;
+                    }
+                    buf.extend_from_slice(&chunk[..n]);
+                    // Concurrent requests may come in the same read.
+                    let mut requests =
+                        serde_json::Deserializer::from_slice(&buf).into_iter::<serde_json::Value>();
+                    let mut answered = vec![];
+                    while let Some(Ok(request)) = requests.next() {
+                        answered.push(request);
+                    }
+                    let consumed = requests.byte_offset();
+                    buf.drain(..consumed);
+                    for request in answered {
+                        let response = serde_json::json!({
+                            "jsonrpc": "2.0",
+                            "id": request["id"],
+                            "result": "0x1",
+                        });
+                        stream
+                            .write_all(response.to_string().as_bytes())
+                            .await
+                            .unwrap();
+                        if close_after_answer {
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+    });
+}
+
+/// Answer `0x1` over a websocket, the first connection is closed after one answer.
+#[cfg(test)]
+async fn spawn_flaky_ws_node() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let mut connections = 0;
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            connections += 1;
+            let close_after_answer = connections == 1;
+            tokio::spawn(async move {
+                let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+                while let Some(Ok(Message::Text(text))) = ws.next().await {
+                    let request: serde_json::Value = serde_json::from_str(&text).unwrap();
+                    let response = serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": request["id"],
+                        "result": "0x1",
+                    });
+                    ws.send(Message::Text(response.to_string())).await.unwrap();
+                    if close_after_answer {
+                        let _ = ws.close(None).await;
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    url
+}
+
+#[tokio::test]
+async fn test_on_ws_reconnect() {
+    let url = spawn_flaky_ws_node().await;
+
+    let transport = Transport::connect(&url).await.unwrap();
+    assert!(matches!(transport, Transport::Ws(_)));
+    let provider = Provider::new(transport);
+
+    assert_eq!(provider.get_chainid().await.unwrap(), 1.into());
+    // The node closed the first connection, the request goes through a new one.
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    assert_eq!(provider.get_chainid().await.unwrap(), 1.into());
+
+    // Sending a tx without a nonce again could send it twice, the caller gets the error.
+    let url = spawn_flaky_ws_node().await;
+    let transport = Transport::connect(&url).await.unwrap();
+    let _: String = transport.request("eth_chainId", ()).await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let tx = serde_json::json!({"from": Address::zero(), "to": Address::zero()});
+    let sent: Result<String, _> = transport.request("eth_sendTransaction", [tx]).await;
+    assert!(sent.is_err());
+    let _: String = transport.request("eth_chainId", ()).await.unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_on_ipc_reconnect() {
+    let path = std::env::temp_dir().join(format!("ethereum-kits-{}.ipc", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    spawn_ipc_node(path.clone(), true);
+
+    let transport = Transport::connect(path.to_str().unwrap()).await.unwrap();
+    assert!(matches!(transport, Transport::Ipc(_)));
+    let provider = Provider::new(transport);
+
+    assert_eq!(provider.get_chainid().await.unwrap(), 1.into());
+    // The node closed the first connection, the request goes through a new one.
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(provider.get_chainid().await.unwrap(), 1.into());
+
+    // Sending a tx without a nonce again could send it twice, the caller gets the error.
+    let transport = Transport::connect(path.to_str().unwrap()).await.unwrap();
+    let _: String = transport.request("eth_chainId", ()).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let tx = serde_json::json!({"from": Address::zero(), "to": Address::zero()});
+    let sent: Result<String, _> = transport.request("eth_sendTransaction", [tx]).await;
+    assert!(sent.is_err());
+    let _: String = transport.request("eth_chainId", ()).await.unwrap();
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn test_on_unsupported_endpoint() {
+    assert!(ipc_path("ipc:///tmp/geth.ipc").is_some());
+    assert!(ipc_path("/tmp/geth.ipc").is_some());
+    assert!(Transport::connect("ftp://localhost").await.is_err());
+    assert!(matches!(
+        Transport::connect("http://localhost:8545").await.unwrap(),
+        Transport::Http(_)
+    ));
+}
@@ -0,0 +1,389 @@
+use anyhow::{anyhow, Result};
+use ethers::{
+    prelude::k256::ecdsa::SigningKey,
+    providers::JsonRpcClient,
+    signers::Wallet,
+    types::{
+        transaction::eip2718::TypedTransaction, BlockNumber, Bytes, Eip1559TransactionRequest,
+    },
+};
+
+use crate::{
+    ethereum_client::{EthereumClient, EthereumClientTrait},
+    fee_oracle::{median_reward, FeeOracle, FeeStrategy, BASE_FEE_MULTIPLIER},
+};
+use account::{Account, AccountSigner, NonceManager, NonceProvider};
+
+#[cfg(test)]
+use {
+    crate::{
+        mock_node::{test_account, MockNode, MockReply},
+        retry::RetryPolicy,
+    },
+    account::RemoteSigner,
+    ethers::{
+        providers::{MockProvider, Provider},
+        types::{Address, TransactionRequest, U256},
+    },
+    serde_json::json,
+    std::{
+        collections::HashSet,
+        sync::{Arc, Mutex},
+        time::Duration,
+    },
+};
+
+/// Number of blocks of `eth_feeHistory` used to pick the priority fee.
+const FEE_HISTORY_BLOCKS: u64 = 10;
+/// Reward percentile of `eth_feeHistory` used as the priority fee.
+const FEE_HISTORY_REWARD_PERCENTILE: f64 = 50.0;
+
+/// Fill the missing fields of a legacy, EIP-2930 or EIP-1559 transaction and sign it.
+///
+/// `from` is the account, `chainId` comes from `eth_chainId`, the nonce from the nonce manager
+/// or the pending nonce on chain, fees from `eth_gasPrice` or `eth_feeHistory`, and the gas
+/// limit from `eth_estimateGas`. Fields already set are kept.
+pub struct TxBuilder<'a, M: JsonRpcClient, S = Wallet<SigningKey>> {
+    client: &'a EthereumClient<M>,
+    account: &'a Account<S>,
+    nonce_manager: Option<&'a NonceManager>,
+    fee_oracle: Option<(&'a FeeOracle<M>, FeeStrategy)>,
+    tx: TypedTransaction,
+}
+
+impl<'a, M: JsonRpcClient, S: AccountSigner> TxBuilder<'a, M, S> {
+    pub fn new(
+        client: &'a EthereumClient<M>,
+        account: &'a Account<S>,
+        tx: TypedTransaction,
+    ) -> Self {
+        Self {
+            client,
+            account,
+            nonce_manager: None,
+            fee_oracle: None,
+            tx,
+        }
+    }
+
+    /// Reserve the nonce from the manager instead of asking the chain.
+    pub fn with_nonce_manager(mut self, nonce_manager: &'a NonceManager) -> Self {
+        self.nonce_manager = Some(nonce_manager);
+        self
+    }
+
+    /// Price EIP-1559 transactions with the fee oracle instead of a plain `eth_feeHistory`.
+    pub fn with_fee_oracle(mut self, fee_oracle: &'a FeeOracle<M>, strategy: FeeStrategy) -> Self {
+        self.fee_oracle = Some((fee_oracle, strategy));
+        self
+    }
+
+    /// Fill the missing fields. A nonce reserved from the nonce manager is released on failure.
+    pub async fn fill(self) -> Result<TypedTransaction> {
+        let mut tx = self.tx.clone();
+        if tx.from().is_none() {
+            tx.set_from(self.account.address);
+        }
+
+        if tx.chain_id().is_none() {
+            let chain_id = self.client.chain_id().await?;
+            tx.set_chain_id(chain_id.as_u64());
+        }
+
+        let mut reserved_nonce = None;
+        if tx.nonce().is_none() {
+            let nonce = match self.nonce_manager {
+                Some(nonce_manager) => {
+                    let nonce = nonce_manager.reserve();
+                    reserved_nonce = Some(nonce);
+                    nonce
+                }
+                None => self.client.pending_nonce(self.account.address).await?,
+            };
+            tx.set_nonce(nonce);
+        }
+
+        match self.fill_fees_and_gas(&mut tx).await {
+            Ok(()) => Ok(tx),
+            Err(e) => {
+                if let (Some(nonce_manager), Some(nonce)) = (self.nonce_manager, reserved_nonce) {
+                    nonce_manager.release(nonce);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Fill the missing fields and sign, return the signed raw transaction.
+    ///
+    /// A nonce reserved from the nonce manager is released when filling or signing fails.
+    pub async fn build_and_sign(self) -> Result<Bytes> {
+        let account = self.account;
+        // `fill` reserves a nonce only for a tx without one.
+        let nonce_manager = self.nonce_manager.filter(|_| self.tx.nonce().is_none());
+        let tx = self.fill().await?;
+        let signed = account.sign_tx(&tx).await;
+        if let (Err(_), Some(nonce_manager), Some(nonce)) = (&signed, nonce_manager, tx.nonce()) {
+            nonce_manager.release(nonce.as_u64());
+        }
+        signed
+    }
+
+    async fn fill_fees_and_gas(&self, tx: &mut TypedTransaction) -> Result<()> {
+        match tx {
+            TypedTransaction::Legacy(_) | TypedTransaction::Eip2930(_) => {
+                if tx.gas_price().is_none() {
+                    let gas_price = self.client.gas_price().await?;
+                    tx.set_gas_price(gas_price);
+                }
+            }
+            TypedTransaction::Eip1559(inner) => self.fill_eip1559_fees(inner).await?,
+            #[allow(unreachable_patterns)]
+            _ => return Err(anyhow!("unsupported transaction type")),
+        }
+
+        if tx.gas().is_none() {
+            let gas = self.client.estimate_gas(tx, None).await?;
+            tx.set_gas(gas);
+        }
+
+        Ok(())
+    }
+
+    async fn fill_eip1559_fees(&self, tx: &mut Eip1559TransactionRequest) -> Result<()> {
+        if tx.max_fee_per_gas.is_some() && tx.max_priority_fee_per_gas.is_some() {
+            return Ok(());
+        }
+
+        if let Some((fee_oracle, strategy)) = self.fee_oracle {
+            let estimate = fee_oracle.estimate(strategy).await?;
+            let priority_fee = tx
+                .max_priority_fee_per_gas
+                .unwrap_or(estimate.max_priority_fee_per_gas);
+            let max_fee = tx
+                .max_fee_per_gas
+                .unwrap_or(estimate.base_fee * BASE_FEE_MULTIPLIER + priority_fee);
+            tx.max_priority_fee_per_gas = Some(priority_fee.min(max_fee));
+            tx.max_fee_per_gas = Some(max_fee);
+            return Ok(());
+        }
+
+        let fee_history = self
+            .client
+            .fee_history(
+                FEE_HISTORY_BLOCKS,
+                BlockNumber::Latest,
+                &[FEE_HISTORY_REWARD_PERCENTILE],
+            )
+            .await?;
+
+        // The last base fee of the history is the one of the next block.
+        let next_base_fee = *fee_history
+            .base_fee_per_gas
+            .last()
+            .ok_or_else(|| anyhow!("empty base fee in fee history"))?;
+
+        let priority_fee = match tx.max_priority_fee_per_gas {
+            Some(priority_fee) => priority_fee,
+            None => median_reward(&fee_history.reward, 0),
+        };
+        let max_fee = tx
+            .max_fee_per_gas
+            .unwrap_or(next_base_fee * BASE_FEE_MULTIPLIER + priority_fee);
+
+        tx.max_priority_fee_per_gas = Some(priority_fee.min(max_fee));
+        tx.max_fee_per_gas = Some(max_fee);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+fn mock_client() -> (EthereumClient<MockProvider>, MockProvider) {
+    let (provider, mock) = Provider::mocked();
+    (
+        EthereumClient::new_with_provider(provider, "mock".to_string()),
+        mock,
+    )
+}
+
+#[tokio::test]
+async fn test_fill_eip1559_tx() {
+    let (client, mock) = mock_client();
+    let account = test_account();
+
+    // MockProvider pops responses from the back.
+    mock.push(U256::from(21_000)).unwrap();
+    mock.push(json!({
+        "oldestBlock": "0x10",
+        "baseFeePerGas": ["0x64", "0x64", "0x6e", "0x78"],
+        "gasUsedRatio": [0.5, 0.6, 0.7],
+        "reward": [["0x0"], ["0x5"], ["0x3"]],
+    }))
+    .unwrap();
+    mock.push(U256::from(7)).unwrap();
+    mock.push(U256::from(1)).unwrap();
+
+    let tx: TypedTransaction = Eip1559TransactionRequest::new()
+        .to(Address::zero())
+        .value(1)
+        .into();
+    let tx = TxBuilder::new(&client, &account, tx).fill().await.unwrap();
+
+    assert_eq!(tx.from(), Some(&account.address));
+    assert_eq!(tx.chain_id(), Some(1.into()));
+    assert_eq!(tx.nonce(), Some(&7.into()));
+    assert_eq!(tx.gas(), Some(&21_000.into()));
+    let TypedTransaction::Eip1559(inner) = tx else {
+        panic!("expected an eip1559 transaction");
+    };
+    assert_eq!(inner.max_priority_fee_per_gas, Some(5.into()));
+    assert_eq!(inner.max_fee_per_gas, Some((0x78 * 2 + 5).into()));
+}
+
+#[tokio::test]
+async fn test_fill_legacy_tx_with_nonce_manager() {
+    let (client, mock) = mock_client();
+    let account = test_account();
+    let nonce_manager = NonceManager::new(account.address);
+    nonce_manager.reset(3);
+
+    mock.push(U256::from(50_000)).unwrap();
+    mock.push(U256::from(1_000_000_000u64)).unwrap();
+    mock.push(U256::from(5)).unwrap();
+
+    let tx: TypedTransaction = TransactionRequest::new().to(Address::zero()).into();
+    let raw_tx = TxBuilder::new(&client, &account, tx)
+        .with_nonce_manager(&nonce_manager)
+        .build_and_sign()
+        .await
+        .unwrap();
+
+    let (tx, _, signer) = account::verify::decode_signed_tx(&raw_tx).unwrap();
+    assert_eq!(signer, account.address);
+    assert_eq!(tx.chain_id(), Some(5.into()));
+    assert_eq!(tx.nonce(), Some(&3.into()));
+    assert_eq!(tx.gas_price(), Some(1_000_000_000u64.into()));
+    assert_eq!(tx.gas(), Some(&50_000.into()));
+    assert_eq!(nonce_manager.next_nonce(), 4);
+}
+
+#[tokio::test]
+async fn test_fill_releases_nonce_on_error() {
+    let (client, mock) = mock_client();
+    let account = test_account();
+    let nonce_manager = NonceManager::new(account.address);
+
+    // eth_gasPrice has no response left and fails.
+    mock.push(U256::from(1)).unwrap();
+
+    let tx: TypedTransaction = TransactionRequest::new().to(Address::zero()).into();
+    let result = TxBuilder::new(&client, &account, tx)
+        .with_nonce_manager(&nonce_manager)
+        .fill()
+        .await;
+
+    assert!(result.is_err());
+    assert_eq!(nonce_manager.reserve(), 0);
+}
+
+#[tokio::test]
+async fn test_build_and_sign_releases_nonce_on_signing_error() {
+    let (client, _) = mock_client();
+    let signer = MockNode::spawn(|_, _| MockReply::error(4001, "user rejected the request"))
+        .await
+        .unwrap();
+    let account = Account::from_signer(RemoteSigner::new(signer.url(), Address::zero()));
+    let nonce_manager = NonceManager::new(account.address);
+
+    // Nothing left to fill but the nonce.
+    let tx: TypedTransaction = TransactionRequest::new()
+        .to(Address::zero())
+        .chain_id(1)
+        .gas_price(1)
+        .gas(21_000)
+        .into();
+    let err = TxBuilder::new(&client, &account, tx)
+        .with_nonce_manager(&nonce_manager)
+        .build_and_sign()
+        .await
+        .unwrap_err();
+
+    assert!(err.to_string().contains("user rejected"));
+    assert_eq!(nonce_manager.reserve(), 0);
+}
+
+#[tokio::test]
+async fn test_fill_retries_rate_limited_reads() {
+    let rate_limited = Arc::new(Mutex::new(HashSet::new()));
+    let node = MockNode::spawn(move |method, _| {
+        // The first request of every method is rate limited.
+        if rate_limited.lock().unwrap().insert(method.to_string()) {
+            return MockReply::Status(429);
+        }
+        match method {
+            "eth_chainId" => MockReply::result(U256::one()),
+            "eth_gasPrice" => MockReply::result(U256::from(1_000_000_000u64)),
+            "eth_estimateGas" => MockReply::result(U256::from(21_000)),
+            _ => MockReply::error(-32601, "method not found"),
+        }
+    })
+    .await
+    .unwrap();
+    let client = EthereumClient::new(node.url())
+        .unwrap()
+        .with_retry_policy(RetryPolicy {
+            initial_backoff: Duration::from_millis(10),
+            ..RetryPolicy::default()
+        });
+    let account = test_account();
+
+    let tx: TypedTransaction = TransactionRequest::new()
+        .to(Address::zero())
+        .nonce(0)
+        .into();
+    let tx = TxBuilder::new(&client, &account, tx).fill().await.unwrap();
+
+    assert_eq!(tx.chain_id(), Some(1.into()));
+    assert_eq!(tx.gas_price(), Some(1_000_000_000u64.into()));
+    assert_eq!(tx.gas(), Some(&21_000.into()));
+    node.assert_called("eth_chainId", 2);
+    node.assert_called("eth_gasPrice", 2);
+    node.assert_called("eth_estimateGas", 2);
+}
+
+#[tokio::test]
+async fn test_fill_eip1559_tx_with_fee_oracle() {
+    let (client, mock) = mock_client();
+    let fee_oracle = FeeOracle::new(client.clone());
+    let account = test_account();
+
+    // MockProvider pops responses from the back.
+    mock.push(U256::from(21_000)).unwrap();
+    mock.push(json!({
+        "oldestBlock": "0x10",
+        "baseFeePerGas": ["0x64", "0x64", "0x6e", "0x78"],
+        "gasUsedRatio": [0.5, 0.6, 0.7],
+        "reward": [["0x1", "0x5", "0x6", "0x7"], ["0x1", "0x5", "0x6", "0x7"], ["0x1", "0x5", "0x6", "0x7"]],
+    }))
+    .unwrap();
+    mock.push(U256::from(0x12)).unwrap();
+
+    let tx: TypedTransaction = Eip1559TransactionRequest::new()
+        .to(Address::zero())
+        .nonce(0)
+        .chain_id(1)
+        .into();
+    let tx = TxBuilder::new(&client, &account, tx)
+        .with_fee_oracle(&fee_oracle, FeeStrategy::Fast)
+        .fill()
+        .await
+        .unwrap();
+
+    let TypedTransaction::Eip1559(inner) = tx else {
+        panic!("expected an eip1559 transaction");
+    };
+    // Same headroom over the base fee as without the oracle.
+    assert_eq!(inner.max_priority_fee_per_gas, Some(6.into()));
+    assert_eq!(inner.max_fee_per_gas, Some((0x78 * 2 + 6).into()));
+}